@@ -0,0 +1,181 @@
+//! Command-line options of the desktop binary.
+//!
+//! Parsed once in `main` for the primary instance and again for every argv a
+//! second instance forwards. Options given here take precedence over the settings
+//! store and the CLI config when picking the server.
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Parser, serde::Serialize)]
+#[command(name = "opencode-desktop", about = "The open source AI coding agent")]
+#[serde(rename_all = "camelCase")]
+pub struct Args {
+    /// Project directory to open
+    pub path: Option<PathBuf>,
+
+    /// Connect to this server instead of the stored or configured one
+    #[arg(long, value_name = "URL", value_parser = parse_server_url)]
+    pub server_url: Option<String>,
+
+    /// Port for the local server, overriding OPENCODE_PORT
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub port: Option<u16>,
+
+    /// Open the project in a new window even if one already shows it
+    #[arg(long)]
+    pub new_window: bool,
+
+    /// Use a separate settings store
+    #[arg(long, value_name = "NAME", value_parser = parse_profile)]
+    pub profile: Option<String>,
+
+    /// Ignore stored settings and do not load project environments
+    #[arg(long)]
+    pub safe_mode: bool,
+
+    /// Log level, also passed on to the local server
+    #[arg(long, value_enum, ignore_case = true)]
+    pub log_level: Option<LogLevel>,
+
+    /// Run the local server without opening a window
+    #[arg(long)]
+    pub headless: bool,
+
+    /// With --headless, also print the generated server password
+    #[arg(long, requires = "headless")]
+    pub print_password: bool,
+}
+
+/// Ordered from least to most severe.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// The value the server's `--log-level` expects.
+    pub fn as_server_arg(self) -> &'static str {
+        match self {
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+}
+
+impl Args {
+    /// Parses a forwarded argv, which includes the executable.
+    pub fn parse_forwarded(argv: &[String]) -> Result<Self, String> {
+        Self::try_parse_from(argv).map_err(|e| e.to_string())
+    }
+
+    /// Options that only take effect when the app starts.
+    pub fn startup_only(&self) -> Vec<&'static str> {
+        [
+            ("--server-url", self.server_url.is_some()),
+            ("--port", self.port.is_some()),
+            ("--profile", self.profile.is_some()),
+            ("--safe-mode", self.safe_mode),
+            ("--log-level", self.log_level.is_some()),
+            ("--headless", self.headless),
+            ("--print-password", self.print_password),
+        ]
+        .into_iter()
+        .filter_map(|(name, given)| given.then_some(name))
+        .collect()
+    }
+}
+
+pub fn parse_server_url(value: &str) -> Result<String, String> {
+    let url = reqwest::Url::parse(value).map_err(|e| format!("invalid URL: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("expected an http:// or https:// URL".to_string());
+    }
+
+    Ok(value.trim_end_matches('/').to_string())
+}
+
+fn parse_profile(value: &str) -> Result<String, String> {
+    let valid = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err("profile names may only contain letters, digits, '-' and '_'".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        let argv: Vec<String> = std::iter::once("opencode-desktop")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect();
+        Args::parse_forwarded(&argv)
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = parse(&[
+            "../app",
+            "--server-url",
+            "http://localhost:4096/",
+            "--port=5000",
+            "--new-window",
+            "--profile",
+            "work",
+            "--safe-mode",
+            "--log-level",
+            "debug",
+            "--headless",
+            "--print-password",
+        ])
+        .unwrap();
+
+        assert_eq!(args.path, Some(PathBuf::from("../app")));
+        assert_eq!(args.server_url.as_deref(), Some("http://localhost:4096"));
+        assert_eq!(args.port, Some(5000));
+        assert!(args.new_window && args.safe_mode && args.headless && args.print_password);
+        assert_eq!(args.profile.as_deref(), Some("work"));
+        assert_eq!(args.log_level, Some(LogLevel::Debug));
+        assert_eq!(args.startup_only().len(), 7);
+
+        assert_eq!(parse(&[]).unwrap(), Args::default());
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        assert!(parse(&["--server-url", "localhost:4096"]).is_err());
+        assert!(parse(&["--server-url", "ftp://example.com"]).is_err());
+        assert!(parse(&["--port", "0"]).is_err());
+        assert!(parse(&["--port", "70000"]).is_err());
+        assert!(parse(&["--profile", "../other"]).is_err());
+        assert!(parse(&["--log-level", "verbose"]).is_err());
+        assert!(parse(&["--print-password"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+}
@@ -1,4 +1,12 @@
-use tauri::{path::BaseDirectory, AppHandle, Manager};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use tauri::{path::BaseDirectory, AppHandle, Manager, Runtime};
 use tauri_plugin_shell::{process::Command, ShellExt};
 
 const CLI_INSTALL_DIR: &str = ".opencode/bin";
@@ -15,11 +23,12 @@ pub struct Config {
     pub server: Option<ServerConfig>,
 }
 
+#[tracing::instrument(name = "config", skip_all)]
 pub async fn get_config(app: &AppHandle) -> Option<Config> {
-    create_command(app, "debug config")
+    create_command(app, &["debug".into(), "config".into()])
         .output()
         .await
-        .inspect_err(|e| eprintln!("Failed to read OC config: {e}"))
+        .inspect_err(|e| tracing::warn!("Failed to read OC config: {e}"))
         .ok()
         .and_then(|out| String::from_utf8(out.stdout.to_vec()).ok())
         .and_then(|s| serde_json::from_str::<Config>(&s).ok())
@@ -33,7 +42,7 @@ fn get_cli_install_path() -> Option<std::path::PathBuf> {
     })
 }
 
-pub fn get_sidecar_path(app: &tauri::AppHandle) -> std::path::PathBuf {
+pub fn get_sidecar_path<R: Runtime>(app: &AppHandle<R>) -> std::path::PathBuf {
     // Get binary with symlinks support
     tauri::process::current_binary(&app.env())
         .expect("Failed to get current binary")
@@ -91,19 +100,11 @@ pub fn install_cli(app: tauri::AppHandle) -> Result<String, String> {
     Ok(install_path.to_string_lossy().to_string())
 }
 
-pub fn sync_cli(app: tauri::AppHandle) -> Result<(), String> {
-    if cfg!(debug_assertions) {
-        println!("Skipping CLI sync for debug build");
-        return Ok(());
-    }
-
-    if !is_cli_installed() {
-        println!("No CLI installation found, skipping sync");
-        return Ok(());
-    }
-
-    let cli_path =
-        get_cli_install_path().ok_or_else(|| "Could not determine CLI install path".to_string())?;
+/// Version of the CLI installed under `~/.opencode/bin`, if there is one.
+pub fn installed_version() -> Result<Option<String>, String> {
+    let Some(cli_path) = get_cli_install_path().filter(|path| path.exists()) else {
+        return Ok(None);
+    };
 
     let output = std::process::Command::new(&cli_path)
         .arg("--version")
@@ -114,37 +115,328 @@ pub fn sync_cli(app: tauri::AppHandle) -> Result<(), String> {
         return Err("Failed to get CLI version".to_string());
     }
 
-    let cli_version_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(version))
+}
+
+/// Version of the bundled sidecar.
+pub async fn sidecar_version(app: &AppHandle) -> Result<String, String> {
+    let output = create_command(app, &["--version".into()])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to get sidecar version: {e}"))?;
+
+    if !output.status.success() {
+        return Err("Failed to get sidecar version".to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn sync_cli(app: tauri::AppHandle) -> Result<(), String> {
+    if cfg!(debug_assertions) {
+        tracing::debug!("Skipping CLI sync for debug build");
+        return Ok(());
+    }
+
+    if !is_cli_installed() {
+        tracing::info!("No CLI installation found, skipping sync");
+        return Ok(());
+    }
+
+    let cli_version_str =
+        installed_version()?.ok_or_else(|| "Could not determine CLI install path".to_string())?;
     let cli_version = semver::Version::parse(&cli_version_str)
         .map_err(|e| format!("Failed to parse CLI version '{}': {}", cli_version_str, e))?;
 
     let app_version = app.package_info().version.clone();
 
     if cli_version >= app_version {
-        println!(
-            "CLI version {} is up to date (app version: {}), skipping sync",
-            cli_version, app_version
+        tracing::info!(
+            "CLI version {cli_version} is up to date (app version: {app_version}), skipping sync"
         );
         return Ok(());
     }
 
-    println!(
-        "CLI version {} is older than app version {}, syncing",
-        cli_version, app_version
-    );
+    tracing::info!("CLI version {cli_version} is older than app version {app_version}, syncing");
 
     install_cli(app)?;
 
-    println!("Synced installed CLI");
+    tracing::info!("Synced installed CLI");
 
     Ok(())
 }
 
-fn get_user_shell() -> String {
-    std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+fn read_all(pipe: Option<impl Read>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut buf);
+    }
+    buf
+}
+
+/// Waits for `child` and collects whichever of its stdout and stderr were piped,
+/// killing it if that takes longer than `timeout`.
+pub fn output_with_timeout(
+    mut child: std::process::Child,
+    timeout: Duration,
+) -> Result<Output, String> {
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        // Read both at once so neither pipe can fill up and block the child
+        let stderr = std::thread::spawn(move || read_all(stderr));
+        let stdout = read_all(stdout);
+        let _ = tx.send((stdout, stderr.join().unwrap_or_default()));
+    });
+
+    let output = rx.recv_timeout(timeout);
+    if output.is_err() {
+        let _ = child.kill();
+    }
+    let status = child.wait();
+
+    let (stdout, stderr) = output.map_err(|_| format!("timed out after {timeout:?}"))?;
+    let status = status.map_err(|e| format!("failed to wait: {e}"))?;
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Reads the whole stdout of `child`, killing it if that takes longer than `timeout`.
+pub fn stdout_with_timeout(
+    child: std::process::Child,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    output_with_timeout(child, timeout).map(|output| output.stdout)
+}
+
+/// How long direnv or mise get to evaluate a project's environment.
+const PROJECT_ENV_TIMEOUT: Duration = Duration::from_secs(10);
+
+const MISE_CONFIG_FILES: &[&str] = &[
+    "mise.toml",
+    ".mise.toml",
+    "mise.local.toml",
+    ".mise/config.toml",
+    ".config/mise.toml",
+    ".tool-versions",
+];
+
+/// Environment a project directory asks for, and where each variable came from.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectEnv {
+    pub dir: PathBuf,
+    pub sources: Vec<ProjectEnvSource>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ProjectEnvSource {
+    /// `dotenv`, `mise` or `direnv`.
+    pub source: &'static str,
+    /// Names of the variables this source set; values are kept out of reports.
+    pub vars: Vec<String>,
+    #[serde(skip)]
+    values: Vec<(String, String)>,
+}
+
+impl ProjectEnvSource {
+    fn new(source: &'static str, values: Vec<(String, String)>) -> Self {
+        Self {
+            source,
+            vars: values.iter().map(|(key, _)| key.clone()).collect(),
+            values,
+        }
+    }
+}
+
+impl ProjectEnv {
+    /// All resolved variables, later sources overriding earlier ones.
+    pub fn vars(&self) -> HashMap<String, String> {
+        self.sources
+            .iter()
+            .flat_map(|source| source.values.iter().cloned())
+            .collect()
+    }
+}
+
+/// Environment the project tools run with, so they are found on the user's `PATH`.
+fn tool_env() -> HashMap<String, String> {
+    #[cfg(unix)]
+    return crate::shell_env::login_env().clone();
+
+    #[cfg(not(unix))]
+    return std::env::vars_os()
+        .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+        .collect();
+}
+
+fn find_program(name: &str, env: &HashMap<String, String>) -> Option<PathBuf> {
+    let (_, path) = env
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("PATH"))?;
+    let name = format!("{name}{}", std::env::consts::EXE_SUFFIX);
+
+    std::env::split_paths(path)
+        .map(|dir| dir.join(&name))
+        .find(|candidate| candidate.is_file())
 }
 
-pub fn create_command(app: &tauri::AppHandle, args: &str) -> Command {
+fn has_config(dir: &Path, files: &[&str]) -> bool {
+    dir.ancestors()
+        .any(|dir| files.iter().any(|file| dir.join(file).is_file()))
+}
+
+fn run_tool(
+    program: &Path,
+    args: &[&str],
+    dir: &Path,
+    env: &HashMap<String, String>,
+) -> Result<Vec<u8>, String> {
+    let child = std::process::Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .env_clear()
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to spawn: {e}"))?;
+
+    let output = output_with_timeout(child, PROJECT_ENV_TIMEOUT)?;
+    // A blocked .envrc or an untrusted mise config fails with nothing on stdout
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => format!("exited with {}", output.status),
+            stderr => format!("exited with {}: {stderr}", output.status),
+        });
+    }
+    Ok(output.stdout)
+}
+
+/// Parses a `{"NAME": "value"}` object; `null` values (unsets) are skipped.
+fn parse_env_json(output: &[u8]) -> Result<Vec<(String, String)>, String> {
+    if output.iter().all(u8::is_ascii_whitespace) {
+        return Ok(Vec::new());
+    }
+
+    let vars: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(output).map_err(|e| format!("invalid JSON: {e}"))?;
+
+    Ok(vars
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.as_str()?.to_string())))
+        .collect())
+}
+
+/// Parses `KEY=value` lines of a `.env` file.
+///
+/// Supports comments, `export` prefixes, single quotes (literal) and double quotes
+/// (with `\n`, `\"` and `\\` escapes).
+fn parse_dotenv(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+
+            let key = key.trim();
+            let valid_key = !key.is_empty()
+                && !key.starts_with('#')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_key {
+                return None;
+            }
+
+            let value = value.trim();
+            let value = if let Some(quoted) = value.strip_prefix('\'') {
+                quoted
+                    .split_once('\'')
+                    .map_or(quoted, |(inner, _)| inner)
+                    .to_string()
+            } else if let Some(quoted) = value.strip_prefix('"') {
+                let mut inner = String::new();
+                let mut chars = quoted.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n') => inner.push('\n'),
+                            Some(c) => inner.push(c),
+                            None => inner.push('\\'),
+                        },
+                        c => inner.push(c),
+                    }
+                }
+                inner
+            } else {
+                value
+                    .split_once(" #")
+                    .map_or(value, |(value, _)| value)
+                    .trim_end()
+                    .to_string()
+            };
+
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+/// Resolves the environment `dir` asks for through `.env`, mise and direnv.
+///
+/// Later sources win: a `.env` file is applied first, then `mise env`, then
+/// `direnv export`. Tools that are missing, fail or time out are skipped.
+pub fn resolve_project_env(dir: &Path) -> ProjectEnv {
+    let mut sources = Vec::new();
+
+    if let Ok(contents) = std::fs::read_to_string(dir.join(".env")) {
+        sources.push(ProjectEnvSource::new("dotenv", parse_dotenv(&contents)));
+    }
+
+    // Capturing the login environment is slow, so only do it once a tool needs it
+    let mut env = None;
+    let tools: [(&'static str, &[&str], &[&str]); 2] = [
+        ("mise", &["env", "--json"], MISE_CONFIG_FILES),
+        ("direnv", &["export", "json"], &[".envrc"]),
+    ];
+
+    for (tool, args, config_files) in tools {
+        if !has_config(dir, config_files) {
+            continue;
+        }
+        let env = env.get_or_insert_with(tool_env);
+        let Some(program) = find_program(tool, env) else {
+            continue;
+        };
+
+        match run_tool(&program, args, dir, env).and_then(|out| parse_env_json(&out)) {
+            Ok(mut values) => {
+                // direnv's own bookkeeping is meaningless outside its shell hook
+                values.retain(|(key, _)| !key.starts_with("DIRENV_"));
+                sources.push(ProjectEnvSource::new(tool, values));
+            }
+            Err(e) => tracing::warn!(
+                "Failed to evaluate {tool} environment in {}: {e}",
+                dir.display()
+            ),
+        }
+    }
+
+    ProjectEnv {
+        dir: dir.to_path_buf(),
+        sources,
+    }
+}
+
+/// Builds the command running the sidecar with `args`, each passed as one argument.
+pub fn create_command<R: Runtime>(app: &AppHandle<R>, args: &[OsString]) -> Command {
     let state_dir = app
         .path()
         .resolve("", BaseDirectory::AppLocalData)
@@ -155,20 +447,134 @@ pub fn create_command(app: &tauri::AppHandle, args: &str) -> Command {
         .shell()
         .sidecar("opencode-cli")
         .unwrap()
-        .args(args.split_whitespace())
+        .args(args)
         .env("OPENCODE_EXPERIMENTAL_ICON_DISCOVERY", "true")
         .env("OPENCODE_CLIENT", "desktop")
         .env("XDG_STATE_HOME", &state_dir);
 
     #[cfg(not(target_os = "windows"))]
-    return {
-        let sidecar = get_sidecar_path(app);
-        let shell = get_user_shell();
-        app.shell()
-            .command(&shell)
-            .env("OPENCODE_EXPERIMENTAL_ICON_DISCOVERY", "true")
-            .env("OPENCODE_CLIENT", "desktop")
-            .env("XDG_STATE_HOME", &state_dir)
-            .args(["-il", "-c", &format!("\"{}\" {}", sidecar.display(), args)])
-    };
+    return app
+        .shell()
+        .command(get_sidecar_path(app))
+        .args(args)
+        .env_clear()
+        .envs(crate::shell_env::login_env())
+        .env("OPENCODE_EXPERIMENTAL_ICON_DISCOVERY", "true")
+        .env("OPENCODE_CLIENT", "desktop")
+        .env("XDG_STATE_HOME", &state_dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let contents = r#"
+# comment
+PLAIN=value
+export EXPORTED=1
+SPACED = padded   # trailing comment
+SINGLE='literal $HOME \n'
+DOUBLE="line\nbreak \"quoted\""
+EMPTY=
+URL=https://example.com/#anchor
+not a var
+=missing
+"#;
+
+        assert_eq!(
+            parse_dotenv(contents),
+            [
+                ("PLAIN", "value"),
+                ("EXPORTED", "1"),
+                ("SPACED", "padded"),
+                ("SINGLE", "literal $HOME \\n"),
+                ("DOUBLE", "line\nbreak \"quoted\""),
+                ("EMPTY", ""),
+                ("URL", "https://example.com/#anchor"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_env_json() {
+        let output =
+            br#"{"JAVA_HOME": "/opt/jdk", "UNSET": null, "PATH": "/opt/jdk/bin:/usr/bin"}"#;
+        let mut vars = parse_env_json(output).unwrap();
+        vars.sort();
+
+        assert_eq!(
+            vars,
+            [("JAVA_HOME", "/opt/jdk"), ("PATH", "/opt/jdk/bin:/usr/bin")]
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+        assert_eq!(parse_env_json(b"\n").unwrap(), []);
+    }
+
+    #[test]
+    fn test_create_command_passes_args_verbatim() {
+        let app = tauri::test::mock_builder()
+            .plugin(tauri_plugin_shell::init())
+            .build(tauri::test::mock_context(tauri::test::noop_assets()))
+            .unwrap();
+
+        let args: Vec<OsString> = [
+            "serve",
+            "--cwd",
+            "/Users/me/My Projects/app",
+            "  leading and trailing  ",
+            "",
+            "it's",
+            "\"double\" quoted",
+            "$HOME `id` $(id) ; rm -rf / && echo | cat > out",
+            "back\\slash\nnewline",
+            "/home/ユーザー/プロジェクト",
+            "café 🚀",
+        ]
+        .map(OsString::from)
+        .into();
+
+        let command = std::process::Command::from(create_command(app.handle(), &args));
+        assert_eq!(command.get_args().collect::<Vec<_>>(), args);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_tool_reports_failure_with_stderr() {
+        let script = "echo 'direnv: error .envrc is blocked' >&2; exit 1";
+        let err = run_tool(
+            Path::new("/bin/sh"),
+            &["-c", script],
+            &std::env::temp_dir(),
+            &HashMap::new(),
+        )
+        .unwrap_err();
+
+        assert!(err.starts_with("exited with"), "{err}");
+        assert!(err.ends_with(": direnv: error .envrc is blocked"), "{err}");
+
+        let output = run_tool(
+            Path::new("/bin/sh"),
+            &["-c", "echo '{}'"],
+            &std::env::temp_dir(),
+            &HashMap::new(),
+        );
+        assert_eq!(output, Ok(b"{}\n".to_vec()));
+    }
+
+    #[test]
+    fn test_resolve_project_env_reads_dotenv() {
+        let dir = TempDir::new("env");
+        std::fs::write(dir.join(".env"), "API_URL=http://localhost\nDEBUG=1\n").unwrap();
+
+        let env = resolve_project_env(&dir);
+
+        assert_eq!(env.sources.len(), 1);
+        assert_eq!(env.sources[0].source, "dotenv");
+        assert_eq!(env.sources[0].vars, ["API_URL", "DEBUG"]);
+        assert_eq!(env.vars()["API_URL"], "http://localhost");
+    }
 }
@@ -0,0 +1,314 @@
+//! Local crash reports for panics in the desktop core.
+//!
+//! A panic hook writes the panic message, a backtrace, the app version, the OS and
+//! the last log lines to `crashes/crash-<time>.txt` under the app data dir, then
+//! hands over to the default hook. On the next launch unseen reports are offered
+//! to the user, who can open one or export a diagnostic bundle that includes them.
+//! Reports never leave the machine unless the user attaches them somewhere.
+//! Panics in async tasks are reported too, though the app survives those.
+//!
+//! Panics before `init` runs in `setup` only reach stderr, as there is no app data
+//! dir to write to yet.
+
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogResult};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::logs::{self, LogState};
+
+const CRASH_DIR: &str = "crashes";
+const REPORT_PREFIX: &str = "crash-";
+const REPORT_EXT: &str = ".txt";
+/// Marks reports the user has been told about.
+const SEEN_EXT: &str = ".seen.txt";
+
+/// Reports kept; older ones are deleted when a new one is written.
+const MAX_REPORTS: usize = 10;
+/// Log entries included in a report.
+const REPORT_LOG_LINES: usize = 50;
+
+static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+struct Reporter {
+    dir: PathBuf,
+    version: String,
+    os: String,
+    log_state: Option<LogState>,
+}
+
+impl Reporter {
+    fn report(&self, info: &PanicHookInfo<'_>, now_ms: u64) -> String {
+        let thread = std::thread::current();
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown location".to_string());
+
+        let mut report = String::new();
+        let _ = writeln!(report, "OpenCode Desktop panicked");
+        let _ = writeln!(report, "Time: {}", logs::format_timestamp(now_ms));
+        let _ = writeln!(
+            report,
+            "Version: {} (tauri {})",
+            self.version,
+            tauri::VERSION
+        );
+        let _ = writeln!(report, "OS: {}", self.os);
+        let _ = writeln!(report, "Thread: {}", thread.name().unwrap_or("<unnamed>"));
+        let _ = writeln!(report, "Panic: {} at {location}", panic_message(info));
+        let _ = writeln!(report, "\nBacktrace:\n{}", Backtrace::force_capture());
+
+        // The panic may have happened with the log buffer locked
+        let recent = self
+            .log_state
+            .as_ref()
+            .and_then(|log_state| log_state.try_recent(REPORT_LOG_LINES));
+        match recent {
+            Some(entries) => {
+                let _ = writeln!(report, "Last log lines:");
+                for entry in entries {
+                    let _ = writeln!(report, "{entry}");
+                }
+            }
+            None => {
+                let _ = writeln!(report, "Last log lines: unavailable");
+            }
+        }
+
+        match &self.log_state {
+            // `add_secret` may have been holding the redactor's lock
+            Some(log_state) => log_state
+                .redactor()
+                .redact_without_blocking(&report)
+                .into_owned(),
+            None => report,
+        }
+    }
+
+    fn write(&self, info: &PanicHookInfo<'_>) -> std::io::Result<PathBuf> {
+        let now_ms = logs::now_ms();
+        std::fs::create_dir_all(&self.dir)?;
+
+        let path = self.dir.join(report_name(now_ms));
+        std::fs::write(&path, self.report(info, now_ms))?;
+        prune(&self.dir);
+        Ok(path)
+    }
+}
+
+fn panic_message(info: &PanicHookInfo<'_>) -> String {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+fn report_name(now_ms: u64) -> String {
+    let timestamp = logs::format_timestamp(now_ms).replace([':', '.'], "-");
+    format!("{REPORT_PREFIX}{timestamp}{REPORT_EXT}")
+}
+
+/// Crash reports in `dir`, oldest first.
+fn reports(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut reports: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(REPORT_PREFIX) && name.ends_with(REPORT_EXT))
+        })
+        .collect();
+    // Names start with the time they were written
+    reports.sort_by_key(|path| path.file_name().map(|name| name.to_owned()));
+    reports
+}
+
+fn is_seen(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(SEEN_EXT)
+}
+
+fn mark_seen(path: &Path) -> std::io::Result<PathBuf> {
+    let name = path.to_string_lossy();
+    let seen = PathBuf::from(format!(
+        "{}{SEEN_EXT}",
+        name.strip_suffix(REPORT_EXT).unwrap_or(&name)
+    ));
+    std::fs::rename(path, &seen)?;
+    Ok(seen)
+}
+
+fn prune(dir: &Path) {
+    let reports = reports(dir);
+    let excess = reports.len().saturating_sub(MAX_REPORTS);
+    for path in &reports[..excess] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// The directory crash reports are written to.
+pub fn crash_dir(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join(CRASH_DIR))
+}
+
+/// Every crash report on disk, oldest first, for diagnostic bundles.
+pub fn all_reports(app: &AppHandle) -> Vec<PathBuf> {
+    crash_dir(app).map(|dir| reports(&dir)).unwrap_or_default()
+}
+
+/// Installs the panic hook. Called early in `setup`, once `LogState` exists.
+pub fn init(app: &AppHandle) {
+    let Some(dir) = crash_dir(app) else {
+        tracing::warn!("No app data dir, crash reports are disabled");
+        return;
+    };
+
+    let reporter = Reporter {
+        dir,
+        version: app.package_info().version.to_string(),
+        os: format!(
+            "{} {} {}",
+            tauri_plugin_os::platform(),
+            tauri_plugin_os::version(),
+            tauri_plugin_os::arch()
+        ),
+        log_state: app.try_state::<LogState>().map(|s| s.inner().clone()),
+    };
+    if REPORTER.set(reporter).is_err() {
+        return;
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(reporter) = REPORTER.get() {
+            match reporter.write(info) {
+                Ok(path) => eprintln!("Crash report written to {}", path.display()),
+                Err(e) => eprintln!("Failed to write crash report: {e}"),
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+/// Tells the user about panics since the last launch and offers to open the latest report.
+pub fn offer_reports(app: &AppHandle) {
+    let Some(dir) = crash_dir(app) else {
+        return;
+    };
+    let unseen: Vec<PathBuf> = reports(&dir)
+        .into_iter()
+        .filter(|path| !is_seen(path))
+        .collect();
+    let Some(latest) = unseen.last() else {
+        return;
+    };
+
+    tracing::warn!(
+        "Found {} crash report(s) from a previous run, latest {}",
+        unseen.len(),
+        latest.display()
+    );
+
+    let mut latest_seen = latest.clone();
+    for path in &unseen {
+        match mark_seen(path) {
+            Ok(seen) if path == latest => latest_seen = seen,
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to mark {} as seen: {e}", path.display()),
+        }
+    }
+
+    if app
+        .try_state::<crate::Args>()
+        .is_some_and(|args| args.headless)
+    {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        const OPEN: &str = "Open Report";
+        const EXPORT: &str = "Export Diagnostics";
+
+        let res = app
+            .dialog()
+            .message(format!(
+                "OpenCode hit an internal error last time. A crash report was saved to\n{}\n\n\
+                 Nothing has been sent anywhere. You can attach the report or a diagnostic \
+                 bundle to a bug report.",
+                latest_seen.display()
+            ))
+            .title("OpenCode Internal Error")
+            .buttons(MessageDialogButtons::YesNoCancelCustom(
+                OPEN.to_string(),
+                EXPORT.to_string(),
+                "Dismiss".to_string(),
+            ))
+            .blocking_show_with_result();
+
+        match res {
+            MessageDialogResult::Custom(name) if name == OPEN => {
+                if let Err(e) = app
+                    .opener()
+                    .open_path(latest_seen.to_string_lossy(), None::<&str>)
+                {
+                    tracing::warn!("Failed to open {}: {e}", latest_seen.display());
+                }
+            }
+            MessageDialogResult::Custom(name) if name == EXPORT => {
+                if let Err(e) = crate::diagnostics::export(&app).await {
+                    tracing::warn!("Failed to export diagnostics: {e}");
+                }
+            }
+            _ => {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_reports_are_listed_pruned_and_marked_seen() {
+        let dir = TempDir::new("crashes");
+        std::fs::write(dir.join("unrelated.txt"), "").unwrap();
+
+        for i in 0..MAX_REPORTS as u64 + 2 {
+            std::fs::write(dir.join(report_name(1_792_229_696_000 + i * 1000)), "").unwrap();
+        }
+        prune(&dir);
+
+        let listed = reports(&dir);
+        assert_eq!(listed.len(), MAX_REPORTS);
+        assert!(
+            listed[0]
+                .to_string_lossy()
+                .ends_with("crash-2026-10-17T09-34-58-000Z.txt")
+        );
+
+        let seen = mark_seen(&listed[0]).unwrap();
+        assert!(is_seen(&seen));
+        assert!(seen.to_string_lossy().ends_with("09-34-58-000Z.seen.txt"));
+        assert_eq!(reports(&dir).len(), MAX_REPORTS);
+        assert_eq!(
+            reports(&dir).iter().filter(|p| !is_seen(p)).count(),
+            MAX_REPORTS - 1
+        );
+    }
+}
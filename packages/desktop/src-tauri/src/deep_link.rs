@@ -0,0 +1,301 @@
+//! `opencode://` links.
+//!
+//! The scheme is registered through the deep-link plugin config (a `MimeType`
+//! entry in the Linux desktop file, a URL type on macOS, a registry key on
+//! Windows). Linux and Windows start a new process with the link as its argument,
+//! which the single-instance plugin forwards to `instance::forwarded`; macOS
+//! delivers links to the running app through the plugin's open-url event. Either
+//! way the link is validated here, routed to the window it concerns and emitted
+//! there as `app://deep-link`.
+//!
+//! Any web page can trigger a link, so nothing a link asks for that runs code or
+//! changes servers happens without the user confirming it: opening a project not
+//! already open starts a sidecar in it, which loads its environment and config.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogResult};
+
+use crate::args::parse_server_url;
+use crate::servers::{self, MAIN_WINDOW};
+use crate::{instance, project};
+
+pub const SCHEME: &str = "opencode";
+pub const DEEP_LINK_EVENT: &str = "app://deep-link";
+
+/// Longest session id accepted from a link.
+const MAX_SESSION_ID_LEN: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DeepLink {
+    /// `opencode://open?path=/abs/dir`
+    Open { path: PathBuf },
+    /// `opencode://session/<id>`, optionally with `?path=` naming its project.
+    Session { id: String, path: Option<PathBuf> },
+    /// `opencode://connect?url=https://host:port`
+    Connect { url: String },
+}
+
+/// Payload of `app://deep-link`.
+#[derive(Clone, Debug, serde::Serialize)]
+struct DeepLinkRequest {
+    url: String,
+    link: DeepLink,
+}
+
+/// Whether a command-line argument is an `opencode://` link rather than a path.
+pub fn is_link(arg: &str) -> bool {
+    arg.get(..SCHEME.len() + 1)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(&format!("{SCHEME}:")))
+}
+
+fn query(url: &Url, key: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+        .filter(|v| !v.is_empty())
+}
+
+fn project_path(url: &Url) -> Result<Option<PathBuf>, String> {
+    let Some(path) = query(url, "path").map(PathBuf::from) else {
+        return Ok(None);
+    };
+
+    // There is no working directory to resolve a relative path against
+    if !path.is_absolute() {
+        return Err(format!("path must be absolute: {}", path.display()));
+    }
+    Ok(Some(path))
+}
+
+impl DeepLink {
+    pub fn parse(link: &str) -> Result<Self, String> {
+        let url = Url::parse(link).map_err(|e| format!("invalid link: {e}"))?;
+        if url.scheme() != SCHEME {
+            return Err(format!("not an {SCHEME}:// link"));
+        }
+
+        let segments: Vec<_> = url
+            .path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        match (url.host_str(), segments.as_slice()) {
+            (Some("open"), []) => {
+                let path = project_path(&url)?.ok_or("open link is missing path")?;
+                Ok(Self::Open { path })
+            }
+            (Some("session"), [id]) => {
+                let valid = id.len() <= MAX_SESSION_ID_LEN
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if !valid {
+                    return Err(format!("invalid session id: {id}"));
+                }
+
+                Ok(Self::Session {
+                    id: id.to_string(),
+                    path: project_path(&url)?,
+                })
+            }
+            (Some("connect"), []) => {
+                let url = query(&url, "url").ok_or("connect link is missing url")?;
+                Ok(Self::Connect {
+                    url: parse_server_url(&url)?,
+                })
+            }
+            _ => Err(format!("unsupported link: {link}")),
+        }
+    }
+}
+
+/// Asks the user to confirm what a link wants to do; `action` labels the button.
+fn confirm(app: &AppHandle, title: &str, message: String, action: &str) -> bool {
+    let res = app
+        .dialog()
+        .message(message)
+        .title(title)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            action.to_string(),
+            "Cancel".to_string(),
+        ))
+        .blocking_show_with_result();
+
+    matches!(res, MessageDialogResult::Custom(name) if name == action)
+}
+
+/// Asks before switching servers, so a link alone cannot redirect the app.
+fn confirm_connect(app: &AppHandle, url: &str) -> bool {
+    confirm(
+        app,
+        "Connect to Server",
+        format!(
+            "A link asks OpenCode to connect to the server\n{url}\n\nOnly connect to servers you trust."
+        ),
+        "Connect",
+    )
+}
+
+/// Asks before opening a project, so a link alone cannot run a project's setup.
+fn confirm_open(app: &AppHandle, dir: &Path) -> bool {
+    confirm(
+        app,
+        "Open Project",
+        format!(
+            "A link asks OpenCode to open the project\n{}\n\nOpening it loads the folder's \
+             environment (direnv, mise, .env) and OpenCode config. Only open folders you trust.",
+            dir.display()
+        ),
+        "Open",
+    )
+}
+
+/// Opens the project a link names once the user confirms, then delivers `request`
+/// to its window, or to `fallback` if it cannot be opened.
+fn open_project(app: &AppHandle, path: &Path, fallback: Option<String>, request: DeepLinkRequest) {
+    let dir = match project::resolve_dir(path, Path::new("/")) {
+        Ok(dir) => dir,
+        Err(e) => {
+            tracing::warn!("Failed to open deep link project: {e}");
+            instance::deliver(app, fallback, DEEP_LINK_EVENT, &request);
+            return;
+        }
+    };
+
+    // A project that is already open has nothing left to confirm
+    if let Some(label) = instance::existing_window(app, &dir) {
+        instance::deliver(app, Some(label), DEEP_LINK_EVENT, &request);
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if !confirm_open(&app, &dir) {
+            return;
+        }
+
+        let label = instance::window_for(&app, &dir, false)
+            .inspect_err(|e| tracing::warn!("Failed to open deep link project: {e}"))
+            .ok()
+            .or(fallback);
+        instance::deliver(&app, label, DEEP_LINK_EVENT, &request);
+    });
+}
+
+/// Validates `link` and routes it to the window it concerns.
+pub fn handle(app: &AppHandle, link: &str) {
+    let parsed = match DeepLink::parse(link) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            tracing::warn!("Ignoring deep link: {e}");
+            return;
+        }
+    };
+    tracing::info!("Opening deep link {parsed:?}");
+
+    let request = DeepLinkRequest {
+        url: link.to_string(),
+        link: parsed.clone(),
+    };
+
+    let focused = || instance::focused_window(app).map(|window| window.label().to_string());
+
+    match &parsed {
+        DeepLink::Open { path } => open_project(app, path, None, request),
+        DeepLink::Session {
+            path: Some(path), ..
+        } => open_project(app, path, focused(), request),
+        DeepLink::Session { path: None, .. } => {
+            instance::deliver(app, focused(), DEEP_LINK_EVENT, &request);
+        }
+        DeepLink::Connect { url } => {
+            let app = app.clone();
+            let url = url.clone();
+            tauri::async_runtime::spawn(async move {
+                let window = instance::focused_window(&app);
+                let label = window
+                    .map(|window| window.label().to_string())
+                    .unwrap_or_else(|| MAIN_WINDOW.to_string());
+
+                if !confirm_connect(&app, &url) {
+                    return;
+                }
+
+                let res = match servers::state_for(&app, &label) {
+                    Ok(state) => crate::switch_server(&app, &state, &url).await,
+                    Err(e) => Err(e),
+                };
+                match res {
+                    Ok(_) => instance::deliver(&app, Some(label), DEEP_LINK_EVENT, &request),
+                    Err(e) => tracing::warn!("Failed to connect to {url} from deep link: {e}"),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_links() {
+        assert_eq!(
+            DeepLink::parse("opencode://open?path=%2Fhome%2Fme%2Fmy%20app"),
+            Ok(DeepLink::Open {
+                path: PathBuf::from("/home/me/my app")
+            })
+        );
+        assert_eq!(
+            DeepLink::parse("opencode://session/ses_01JX-abc"),
+            Ok(DeepLink::Session {
+                id: "ses_01JX-abc".to_string(),
+                path: None
+            })
+        );
+        assert_eq!(
+            DeepLink::parse("opencode://session/ses_1?path=/src/app"),
+            Ok(DeepLink::Session {
+                id: "ses_1".to_string(),
+                path: Some(PathBuf::from("/src/app"))
+            })
+        );
+        assert_eq!(
+            DeepLink::parse("opencode://connect?url=https%3A%2F%2Fexample.com%3A4096%2F"),
+            Ok(DeepLink::Connect {
+                url: "https://example.com:4096".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_links() {
+        for link in [
+            "https://open?path=/src/app",
+            "opencode://open",
+            "opencode://open?path=relative/dir",
+            "opencode://session/",
+            "opencode://session/a%2F..%2Fb",
+            "opencode://session/bad%20id",
+            "opencode://session/a/b",
+            "opencode://connect?url=file:///etc/passwd",
+            "opencode://connect",
+            "opencode://unknown",
+            "not a url",
+        ] {
+            assert!(DeepLink::parse(link).is_err(), "{link} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_is_link() {
+        assert!(is_link("opencode://open?path=/x"));
+        assert!(is_link("OpenCode://session/1"));
+        assert!(!is_link("/home/me/opencode"));
+        assert!(!is_link("open"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Health monitoring of the connected server.
+//!
+//! `check_server_health` only gates startup. Once a server is ready, this
+//! monitor keeps polling `/global/health`, records the latency and the number of
+//! consecutive failures in the server lifecycle, and emits `server://degraded` /
+//! `server://recovered` when the server stops or resumes answering. A local
+//! sidecar that keeps failing is killed, so the supervisor restarts it like any
+//! other crash.
+
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager};
+
+use crate::lifecycle::{self, ServerPhase};
+use crate::servers::{self, Servers};
+use crate::{ServerState, check_server_health, get_duration_setting};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const INTERVAL_KEY: &str = "healthCheckIntervalMs";
+const INTERVAL_ENV: &str = "OPENCODE_HEALTH_INTERVAL_MS";
+
+/// Consecutive failed checks after which a wedged local sidecar is restarted.
+const RESTART_AFTER_FAILURES: u32 = 3;
+
+fn interval(app: &AppHandle) -> Duration {
+    get_duration_setting(app, INTERVAL_KEY, INTERVAL_ENV)
+        .filter(|d| !d.is_zero())
+        .unwrap_or(DEFAULT_INTERVAL)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HealthChange {
+    Degraded,
+    Recovered,
+}
+
+/// Consecutive-failure bookkeeping for one server.
+#[derive(Default)]
+struct HealthTracker {
+    failures: u32,
+}
+
+impl HealthTracker {
+    /// Records the outcome of a check (`None` for a failed one) and reports
+    /// whether the server just became degraded or recovered.
+    fn record(&mut self, latency: Option<Duration>) -> Option<HealthChange> {
+        let was_degraded = self.failures > 0;
+
+        match latency {
+            Some(_) => {
+                self.failures = 0;
+                was_degraded.then_some(HealthChange::Recovered)
+            }
+            None => {
+                self.failures += 1;
+                (!was_degraded).then_some(HealthChange::Degraded)
+            }
+        }
+    }
+}
+
+/// Payload of `server://degraded` and `server://recovered`.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HealthReport {
+    url: String,
+    latency_ms: Option<u64>,
+    consecutive_failures: u32,
+}
+
+/// Polls the server behind `state` for as long as a window uses it.
+#[tracing::instrument(name = "health", skip_all)]
+pub async fn monitor(app: AppHandle, state: ServerState) {
+    let mut tracker = HealthTracker::default();
+    let mut monitored_url = None;
+
+    loop {
+        tokio::time::sleep(interval(&app)).await;
+
+        if app.state::<Servers>().windows_of(&state).is_empty() {
+            return;
+        }
+
+        let phase = state.lifecycle.lock().unwrap().phase;
+        // Startup and restarts run their own health checks
+        if !matches!(phase, ServerPhase::Ready | ServerPhase::Unhealthy) {
+            continue;
+        }
+        let Some(data) = state.ready_data() else {
+            continue;
+        };
+
+        if monitored_url.as_ref() != Some(&data.url) {
+            tracker = HealthTracker::default();
+            monitored_url = Some(data.url.clone());
+        }
+
+        let generation = state.generation();
+        let started = Instant::now();
+        let healthy = check_server_health(&data.url, data.password.as_deref()).await;
+        let latency = healthy.then(|| started.elapsed());
+
+        // The server was stopped or replaced while the check was in flight
+        if state.generation() != generation {
+            continue;
+        }
+
+        let change = tracker.record(latency);
+        {
+            let mut lifecycle = state.lifecycle.lock().unwrap();
+            lifecycle.latency = latency;
+            lifecycle.failures = tracker.failures;
+        }
+
+        let report = HealthReport {
+            url: data.url.clone(),
+            latency_ms: latency.map(|d| d.as_millis() as u64),
+            consecutive_failures: tracker.failures,
+        };
+
+        match change {
+            Some(HealthChange::Degraded) => {
+                tracing::warn!("Server {} stopped responding to health checks", data.url);
+                lifecycle::transition(&app, &state, ServerPhase::Unhealthy, |l| {
+                    l.message = Some("Server is not responding".to_string());
+                });
+                servers::emit(&app, &state, "server://degraded", &report);
+            }
+            Some(HealthChange::Recovered) => {
+                tracing::info!("Server {} is responding again", data.url);
+                lifecycle::transition(&app, &state, ServerPhase::Ready, |_| {});
+                servers::emit(&app, &state, "server://recovered", &report);
+            }
+            None => {}
+        }
+
+        if tracker.failures < RESTART_AFTER_FAILURES {
+            continue;
+        }
+
+        // Only a sidecar we spawned has a child; its supervisor takes the exit as a crash
+        let child = state.child.lock().unwrap().take();
+        if let Some(child) = child {
+            let pid = child.pid();
+            tracing::warn!(
+                "Sidecar {pid} failed {} health checks in a row, killing it",
+                tracker.failures
+            );
+            let _ = child.kill();
+            crate::release_child(&app, pid);
+            tracker = HealthTracker::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_reports_transitions_once() {
+        let mut tracker = HealthTracker::default();
+        let ok = Some(Duration::from_millis(5));
+
+        assert_eq!(tracker.record(ok), None);
+        assert_eq!(tracker.record(None), Some(HealthChange::Degraded));
+        assert_eq!(tracker.record(None), None);
+        assert_eq!(tracker.failures, 2);
+        assert_eq!(tracker.record(ok), Some(HealthChange::Recovered));
+        assert_eq!(tracker.failures, 0);
+        assert_eq!(tracker.record(ok), None);
+    }
+}
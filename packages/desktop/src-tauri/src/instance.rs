@@ -0,0 +1,201 @@
+//! Launch requests from the command line.
+//!
+//! The primary instance and any second instance forwarded to it by the
+//! single-instance plugin parse their argv with the same `Args` parser. A forwarded
+//! request opens (or focuses) the window of the project it names, and is then
+//! emitted to that window as `app://second-instance`. Forwarded `opencode://`
+//! links are handed to `deep_link`.
+
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+
+use crate::args::Args;
+use crate::servers::{self, MAIN_WINDOW};
+use crate::{deep_link, project};
+
+pub const SECOND_INSTANCE_EVENT: &str = "app://second-instance";
+
+/// What an invocation of the desktop binary asks for.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchRequest {
+    /// argv of the invocation, including the executable.
+    pub args: Vec<String>,
+    /// Working directory of the invocation.
+    pub cwd: PathBuf,
+    pub options: Args,
+    /// Project directory named on the command line, resolved against `cwd`.
+    pub project: Option<PathBuf>,
+}
+
+impl LaunchRequest {
+    pub fn new(args: Vec<String>, cwd: PathBuf, options: Args) -> Self {
+        // Links are handled by `deep_link`, not opened as directories
+        let path = options
+            .path
+            .as_deref()
+            .filter(|path| !path.to_str().is_some_and(deep_link::is_link));
+        let project = path.and_then(|path| {
+            project::resolve_dir(path, &cwd)
+                .inspect_err(|e| tracing::warn!("Ignoring project argument: {e}"))
+                .ok()
+        });
+
+        Self {
+            args,
+            cwd,
+            options,
+            project,
+        }
+    }
+
+    pub fn parse(args: Vec<String>, cwd: PathBuf) -> Result<Self, String> {
+        let options = Args::parse_forwarded(&args)?;
+        Ok(Self::new(args, cwd, options))
+    }
+}
+
+pub fn focus(window: &WebviewWindow) {
+    let _ = window.unminimize();
+    let _ = window.set_focus();
+}
+
+fn main_project(app: &AppHandle) -> Option<PathBuf> {
+    let state = servers::state_for(app, MAIN_WINDOW).ok()?;
+    state.project_dir.lock().unwrap().clone()
+}
+
+/// The focused window, falling back to the main window.
+pub fn focused_window(app: &AppHandle) -> Option<WebviewWindow> {
+    app.webview_windows()
+        .into_values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| app.get_webview_window(MAIN_WINDOW))
+}
+
+/// The window already showing `dir`, if any.
+pub fn existing_window(app: &AppHandle, dir: &Path) -> Option<String> {
+    if main_project(app).as_deref() == Some(dir) && app.get_webview_window(MAIN_WINDOW).is_some() {
+        return Some(MAIN_WINDOW.to_string());
+    }
+    project::find_window(app, dir).map(|window| window.label().to_string())
+}
+
+/// The window already showing `dir`, or a new one for it.
+pub fn window_for(app: &AppHandle, dir: &Path, new_window: bool) -> Result<String, String> {
+    if !new_window && let Some(label) = existing_window(app, dir) {
+        return Ok(label);
+    }
+
+    project::open_window(app, dir.to_path_buf(), false)
+}
+
+/// Focuses the window `label` and emits `event` to it.
+///
+/// Falls back to the main window, or any window, if `label` is `None` or closed.
+pub fn deliver<S: serde::Serialize + Clone>(
+    app: &AppHandle,
+    label: Option<String>,
+    event: &str,
+    payload: S,
+) {
+    let window = label
+        .and_then(|label| app.get_webview_window(&label))
+        .or_else(|| app.get_webview_window(MAIN_WINDOW))
+        .or_else(|| app.webview_windows().into_values().next());
+    let Some(window) = window else {
+        return;
+    };
+
+    focus(&window);
+    if let Err(e) = app.emit_to(window.label(), event, payload) {
+        tracing::warn!("Failed to emit {event}: {e}");
+    }
+}
+
+/// Handles the argv and cwd a second instance forwarded before exiting.
+pub fn forwarded(app: &AppHandle, args: Vec<String>, cwd: String) {
+    if let Some(link) = args.iter().skip(1).find(|arg| deep_link::is_link(arg)) {
+        deep_link::handle(app, link);
+        return;
+    }
+
+    let request = match LaunchRequest::parse(args, PathBuf::from(cwd)) {
+        Ok(request) => request,
+        Err(e) => {
+            tracing::warn!("Ignoring second instance arguments: {e}");
+            if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+                focus(&window);
+            }
+            return;
+        }
+    };
+    tracing::debug!("Second instance launched with {:?}", request.args);
+
+    let ignored = request.options.startup_only();
+    if !ignored.is_empty() {
+        tracing::info!(
+            "Ignoring startup options of second instance: {}",
+            ignored.join(", ")
+        );
+    }
+
+    // `--new-window` without a path opens the main window's project again
+    let dir = request.project.clone().or_else(|| {
+        request
+            .options
+            .new_window
+            .then(|| main_project(app).unwrap_or_else(|| request.cwd.clone()))
+    });
+
+    let label = match &dir {
+        Some(dir) => match window_for(app, dir, request.options.new_window) {
+            Ok(label) => Some(label),
+            Err(e) => {
+                tracing::warn!("Failed to open forwarded project: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Without a project (or if it failed to open) the request goes to the main window
+    deliver(app, label, SECOND_INSTANCE_EVENT, &request);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_parse_resolves_project_against_forwarded_cwd() {
+        let dir = TempDir::new("instance");
+        let cwd = dir.parent().unwrap().to_path_buf();
+        let name = dir.file_name().unwrap().to_str().unwrap().to_string();
+        let argv = |rest: &[&str]| {
+            std::iter::once("opencode-desktop")
+                .chain(rest.iter().copied())
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        let request = LaunchRequest::parse(argv(&["--new-window", &name]), cwd.clone()).unwrap();
+        assert_eq!(request.project.as_deref(), Some(&*dir));
+        assert!(request.options.new_window);
+
+        let request = LaunchRequest::parse(argv(&[dir.to_str().unwrap()]), "/".into()).unwrap();
+        assert_eq!(request.project.as_deref(), Some(&*dir));
+
+        let request = LaunchRequest::parse(argv(&["does-not-exist"]), cwd.clone()).unwrap();
+        assert_eq!(request.project, None);
+        assert_eq!(
+            LaunchRequest::parse(argv(&[]), cwd.clone())
+                .unwrap()
+                .project,
+            None
+        );
+        assert!(LaunchRequest::parse(argv(&["--bogus"]), cwd).is_err());
+    }
+}
@@ -111,7 +111,7 @@ impl JobObjectState {
                 error: Mutex::new(None),
             },
             Err(e) => {
-                eprintln!("Failed to create job object: {e}");
+                tracing::error!("Failed to create job object: {e}");
                 Self {
                     job: Mutex::new(None),
                     error: Mutex::new(Some(format!("Failed to create job object: {e}"))),
@@ -123,11 +123,11 @@ impl JobObjectState {
     pub fn assign_pid(&self, pid: u32) {
         if let Some(job) = self.job.lock().unwrap().as_ref() {
             if let Err(e) = job.assign_pid(pid) {
-                eprintln!("Failed to assign process {pid} to job object: {e}");
+                tracing::warn!("Failed to assign process {pid} to job object: {e}");
                 *self.error.lock().unwrap() =
                     Some(format!("Failed to assign process to job object: {e}"));
             } else {
-                println!("Assigned process {pid} to job object for automatic cleanup");
+                tracing::debug!("Assigned process {pid} to job object for automatic cleanup");
             }
         }
     }
@@ -1,29 +1,68 @@
+mod args;
 mod cli;
+mod crash_reporter;
+mod deep_link;
+mod diagnostics;
+mod health;
+mod instance;
 #[cfg(windows)]
 mod job_object;
+mod lifecycle;
+mod logs;
+#[cfg(unix)]
+mod pid_file;
+mod project;
+mod redact;
+mod servers;
+#[cfg(unix)]
+mod shell;
+#[cfg(unix)]
+mod shell_env;
+mod shutdown;
+mod supervisor;
+#[cfg(test)]
+mod test_util;
+mod trace;
+#[cfg(unix)]
+pub mod watchdog;
 mod window_customizer;
 
-use cli::{install_cli, sync_cli};
-use futures::FutureExt;
-use futures::future;
+pub use args::Args;
+use cli::{ProjectEnv, install_cli, sync_cli};
 #[cfg(windows)]
 use job_object::*;
 use std::{
-    collections::VecDeque,
-    net::TcpListener,
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
     time::{Duration, Instant},
 };
-use tauri::{AppHandle, LogicalSize, Manager, RunEvent, State, WebviewUrl, WebviewWindow};
+use tauri::{AppHandle, LogicalSize, Manager, RunEvent, WebviewUrl, WebviewWindow, WindowEvent};
+use tauri_plugin_deep_link::DeepLinkExt;
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogResult};
 use tauri_plugin_shell::process::{CommandChild, CommandEvent};
 use tauri_plugin_store::StoreExt;
-use tokio::sync::oneshot;
-
+use tokio::sync::{oneshot, watch};
+pub use trace::init_tracing;
+#[cfg(unix)]
+use watchdog::WatchdogState;
+
+use crate::instance::LaunchRequest;
+use crate::lifecycle::{Lifecycle, ServerPhase, get_server_status};
+use crate::logs::{LogState, LogStream, StreamCapture};
+use crate::servers::{MAIN_WINDOW, Servers};
+use crate::supervisor::{CrashReport, ExitWatch, SidecarExit, wait_exit};
 use crate::window_customizer::PinchZoomDisablePlugin;
 
 const SETTINGS_STORE: &str = "opencode.settings.dat";
 const DEFAULT_SERVER_URL_KEY: &str = "defaultServerUrl";
+/// `connect_to_server` target selecting the bundled sidecar.
+const LOCAL_SERVER: &str = "local";
+
+const UPDATER_ENABLED: bool = option_env!("TAURI_SIGNING_PRIVATE_KEY").is_some();
 
 #[derive(Clone, serde::Serialize)]
 struct ServerReadyData {
@@ -31,79 +70,252 @@ struct ServerReadyData {
     password: Option<String>,
 }
 
+type ServerResult = Result<ServerReadyData, String>;
+
+/// Where the server a window talks to came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+enum ServerSource {
+    /// `--server-url`
+    CommandLine,
+    /// The desktop default server URL setting
+    Setting,
+    /// `server.port` in the CLI config
+    CliConfig,
+    /// A server already listening on the pinned port
+    PinnedPort,
+    /// The bundled sidecar
+    Sidecar,
+    /// A server chosen while running, through `connect_to_server` or a link
+    Switched,
+}
+
 #[derive(Clone)]
 struct ServerState {
     child: Arc<Mutex<Option<CommandChild>>>,
-    status: future::Shared<oneshot::Receiver<Result<ServerReadyData, String>>>,
+    exit: Arc<Mutex<Option<ExitWatch>>>,
+    /// `None` while a server is being selected or (re)started.
+    status: Arc<watch::Sender<Option<ServerResult>>>,
+    /// Bumped whenever the sidecar is stopped on purpose, retiring its supervisor.
+    generation: Arc<AtomicU64>,
+    /// Extra environment passed to every sidecar spawn.
+    env: Arc<Mutex<HashMap<String, String>>>,
+    /// Directory the local sidecar is started in.
+    project_dir: Arc<Mutex<Option<PathBuf>>>,
+    /// Project environment applied to the current sidecar.
+    project_env: Arc<Mutex<Option<ProjectEnv>>>,
+    crash: Arc<Mutex<Option<CrashReport>>>,
+    lifecycle: Arc<Mutex<Lifecycle>>,
+    source: Arc<Mutex<Option<ServerSource>>>,
 }
 
 impl ServerState {
-    pub fn new(
-        child: Option<CommandChild>,
-        status: oneshot::Receiver<Result<ServerReadyData, String>>,
-    ) -> Self {
+    pub fn new() -> Self {
         Self {
-            child: Arc::new(Mutex::new(child)),
-            status: status.shared(),
+            child: Arc::new(Mutex::new(None)),
+            exit: Arc::new(Mutex::new(None)),
+            status: Arc::new(watch::channel(None).0),
+            generation: Arc::new(AtomicU64::new(0)),
+            env: Arc::new(Mutex::new(HashMap::new())),
+            project_dir: Arc::new(Mutex::new(None)),
+            project_env: Arc::new(Mutex::new(None)),
+            crash: Arc::new(Mutex::new(None)),
+            lifecycle: Arc::new(Mutex::new(Lifecycle::default())),
+            source: Arc::new(Mutex::new(None)),
         }
     }
 
     pub fn set_child(&self, child: Option<CommandChild>) {
         *self.child.lock().unwrap() = child;
     }
+
+    pub fn set_status(&self, status: Option<ServerResult>) {
+        self.status.send_replace(status);
+    }
+
+    /// The connection data of the current server, if it is ready.
+    pub fn ready_data(&self) -> Option<ServerReadyData> {
+        self.status.borrow().clone().and_then(Result::ok)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Marks the running sidecar as stopped on purpose, so its exit must not trigger a restart.
+    pub fn stop(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn set_source(&self, source: ServerSource) {
+        *self.source.lock().unwrap() = Some(source);
+    }
+
+    pub fn set_crash(&self, crash: Option<CrashReport>) {
+        *self.crash.lock().unwrap() = crash;
+    }
+
+    /// Waits until a server has been selected, or failed to be.
+    pub async fn wait_ready(&self) -> Result<ServerReadyData, String> {
+        let mut status = self.status.subscribe();
+        let res = status
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| "Failed to get server status".to_string())?
+            .clone();
+
+        res.unwrap()
+    }
+
+    /// Whether `self` and `other` are handles to the same server.
+    pub fn same(&self, other: &ServerState) -> bool {
+        Arc::ptr_eq(&self.status, &other.status)
+    }
 }
 
-#[derive(Clone)]
-struct LogState(Arc<Mutex<VecDeque<String>>>);
+#[tauri::command]
+async fn kill_sidecar(window: WebviewWindow) -> Result<(), String> {
+    let state = servers::state_of(&window)?;
+    shutdown::stop_sidecar(window.app_handle(), &state).await;
+    Ok(())
+}
 
-const MAX_LOG_ENTRIES: usize = 200;
+/// Lines kept in the error shown when the sidecar fails to start.
+const STARTUP_LOG_LINES: usize = 200;
+
+fn get_logs(app: &AppHandle) -> Result<String, String> {
+    let log_state = app.try_state::<LogState>().ok_or("Log state not found")?;
+
+    let logs = log_state.recent(STARTUP_LOG_LINES);
+    Ok(logs
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
 
 #[tauri::command]
-fn kill_sidecar(app: AppHandle) {
-    let Some(server_state) = app.try_state::<ServerState>() else {
-        println!("Server not running");
-        return;
-    };
+async fn ensure_server_ready(window: WebviewWindow) -> Result<ServerReadyData, String> {
+    servers::state_of(&window)?.wait_ready().await
+}
 
-    let Some(server_state) = server_state
-        .child
-        .lock()
-        .expect("Failed to acquire mutex lock")
-        .take()
-    else {
-        println!("Server state missing");
-        return;
-    };
+/// Stops the server behind `state` and selects one again.
+///
+/// `env` replaces the extra environment given to the local sidecar.
+async fn restart(
+    app: &AppHandle,
+    state: &ServerState,
+    env: Option<HashMap<String, String>>,
+) -> Result<ServerReadyData, String> {
+    if let Some(env) = env {
+        *state.env.lock().unwrap() = env;
+    }
 
-    let _ = server_state.kill();
+    shutdown::stop_sidecar(app, state).await;
+    state.set_crash(None);
+    state.set_status(None);
 
-    println!("Killed server");
+    let res = connect(app, state).await;
+    state.set_status(Some(res.clone()));
+
+    res
 }
 
-async fn get_logs(app: AppHandle) -> Result<String, String> {
-    let log_state = app.try_state::<LogState>().ok_or("Log state not found")?;
+/// Stops the window's server and selects one again, without relaunching the app.
+#[tauri::command]
+async fn restart_server(
+    window: WebviewWindow,
+    env: Option<HashMap<String, String>>,
+) -> Result<ServerReadyData, String> {
+    let state = servers::state_of(&window)?;
+    restart(window.app_handle(), &state, env).await
+}
+
+/// Switches `state` (and every window it backs) to another server.
+///
+/// `target` is a server URL or `"local"` for the bundled sidecar. The target is
+/// health-checked before anything is torn down, so a failed switch leaves the
+/// current connection intact. On success `server://changed` tells the webviews to
+/// reconnect.
+async fn switch_server(
+    app: &AppHandle,
+    state: &ServerState,
+    target: &str,
+) -> Result<ServerReadyData, String> {
+    let data = if target == LOCAL_SERVER {
+        let running = state.child.lock().unwrap().is_some();
+        match state.ready_data() {
+            // Already on the local sidecar, so there is nothing to announce
+            Some(data) if running => return Ok(data),
+            _ => setup_server_connection(app, state, None).await?,
+        }
+    } else {
+        let url = target.trim_end_matches('/').to_string();
+        if !check_server_health(&url, None).await {
+            return Err(format!("Could not connect to server: {url}"));
+        }
+
+        shutdown::stop_sidecar(app, state).await;
+
+        lifecycle::transition(app, state, ServerPhase::Ready, |l| {
+            l.url = Some(url.clone());
+            l.pid = None;
+            l.local = false;
+        });
+        state.set_source(ServerSource::Switched);
+
+        ServerReadyData {
+            url,
+            password: None,
+        }
+    };
 
-    let logs = log_state
-        .0
-        .lock()
-        .map_err(|_| "Failed to acquire log lock")?;
+    tracing::info!("Connected to server: {}", data.url);
+    state.set_status(Some(Ok(data.clone())));
+    servers::emit(app, state, "server://changed", &data);
 
-    Ok(logs.iter().cloned().collect::<Vec<_>>().join(""))
+    Ok(data)
 }
 
+/// Switches the window (and any window sharing its server) to another server
+/// without relaunching.
 #[tauri::command]
-async fn ensure_server_ready(state: State<'_, ServerState>) -> Result<ServerReadyData, String> {
-    state
-        .status
-        .clone()
-        .await
-        .map_err(|_| "Failed to get server status".to_string())?
+async fn connect_to_server(
+    window: WebviewWindow,
+    target: String,
+) -> Result<ServerReadyData, String> {
+    let state = servers::state_of(&window)?;
+    switch_server(window.app_handle(), &state, &target).await
+}
+
+/// Which project variables (by name and source) the window's sidecar was started with.
+#[tauri::command]
+fn get_project_env(window: WebviewWindow) -> Result<Option<ProjectEnv>, String> {
+    let state = servers::state_of(&window)?;
+    Ok(state.project_env.lock().unwrap().clone())
+}
+
+/// The settings store of the `--profile` in use.
+fn settings_store(app: &AppHandle) -> String {
+    let profile = app
+        .try_state::<Args>()
+        .and_then(|args| args.profile.clone());
+
+    match profile {
+        Some(profile) => format!("opencode.settings.{profile}.dat"),
+        None => SETTINGS_STORE.to_string(),
+    }
+}
+
+/// Whether the app was started with `--safe-mode`.
+fn safe_mode(app: &AppHandle) -> bool {
+    app.try_state::<Args>().is_some_and(|args| args.safe_mode)
 }
 
 #[tauri::command]
 fn get_default_server_url(app: AppHandle) -> Result<Option<String>, String> {
     let store = app
-        .store(SETTINGS_STORE)
+        .store(settings_store(&app))
         .map_err(|e| format!("Failed to open settings store: {}", e))?;
 
     let value = store.get(DEFAULT_SERVER_URL_KEY);
@@ -116,7 +328,7 @@ fn get_default_server_url(app: AppHandle) -> Result<Option<String>, String> {
 #[tauri::command]
 async fn set_default_server_url(app: AppHandle, url: Option<String>) -> Result<(), String> {
     let store = app
-        .store(SETTINGS_STORE)
+        .store(settings_store(&app))
         .map_err(|e| format!("Failed to open settings store: {}", e))?;
 
     match url {
@@ -135,58 +347,183 @@ async fn set_default_server_url(app: AppHandle, url: Option<String>) -> Result<(
     Ok(())
 }
 
-fn get_sidecar_port() -> u32 {
-    option_env!("OPENCODE_PORT")
-        .map(|s| s.to_string())
-        .or_else(|| std::env::var("OPENCODE_PORT").ok())
-        .and_then(|port_str| port_str.parse().ok())
-        .unwrap_or_else(|| {
-            TcpListener::bind("127.0.0.1:0")
-                .expect("Failed to bind to find free port")
-                .local_addr()
-                .expect("Failed to get local address")
-                .port()
-        }) as u32
+/// Reads a duration in milliseconds from `env`, falling back to the settings store
+/// outside safe mode.
+fn get_duration_setting(app: &AppHandle, key: &str, env: &str) -> Option<Duration> {
+    let from_env = std::env::var(env).ok().and_then(|v| v.trim().parse().ok());
+    let from_store = || {
+        if safe_mode(app) {
+            return None;
+        }
+        app.store(settings_store(app))
+            .ok()
+            .and_then(|store| store.get(key))
+            .and_then(|v| v.as_u64())
+    };
+
+    from_env.or_else(from_store).map(Duration::from_millis)
+}
+
+/// Port pinned through `--port` or `OPENCODE_PORT`; otherwise the sidecar picks its own.
+fn get_sidecar_port(app: &AppHandle) -> Option<u32> {
+    let from_args = app.try_state::<Args>().and_then(|args| args.port);
+
+    from_args.map(u32::from).or_else(|| {
+        std::env::var("OPENCODE_PORT")
+            .ok()
+            .and_then(|port| port.trim().parse().ok())
+    })
+}
+
+/// What a freshly spawned sidecar reports about binding its port.
+#[derive(Debug, PartialEq, Eq)]
+enum SidecarStartup {
+    Listening(u32),
+    AddrInUse,
 }
 
-fn spawn_sidecar(app: &AppHandle, port: u32, password: &str) -> CommandChild {
-    let log_state = app.state::<LogState>();
-    let log_state_clone = log_state.inner().clone();
+/// Recognizes the `serve` command's startup output.
+fn parse_startup_line(line: &str) -> Option<SidecarStartup> {
+    if let Some(url) = line.trim().strip_prefix("opencode server listening on ") {
+        let (_, port) = url.trim_end_matches('/').rsplit_once(':')?;
+        return port.parse().ok().map(SidecarStartup::Listening);
+    }
+
+    let in_use = [
+        "EADDRINUSE",
+        "address already in use",
+        "Failed to start server on port",
+    ];
+    in_use
+        .iter()
+        .any(|marker| line.contains(marker))
+        .then_some(SidecarStartup::AddrInUse)
+}
+
+/// Spawns `serve` on `port` (0 lets the sidecar pick one).
+///
+/// The returned receiver resolves once the sidecar reports whether it could bind,
+/// and is closed if it exits before doing so. Fails if the binary cannot be launched.
+fn spawn_sidecar(
+    app: &AppHandle,
+    state: &ServerState,
+    port: u32,
+    password: &str,
+) -> Result<(CommandChild, ExitWatch, oneshot::Receiver<SidecarStartup>), String> {
+    let log_state = app.state::<LogState>().inner().clone();
+
+    tracing::debug!("spawning sidecar on port {port}");
+
+    let env = state.env.lock().unwrap().clone();
 
-    println!("spawning sidecar on port {port}");
+    let project_dir = state.project_dir.lock().unwrap().clone();
+    let project_dir = project_dir.unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
 
-    let (mut rx, child) = cli::create_command(app, format!("serve --port {port}").as_str())
+    let project_env = if safe_mode(app) {
+        tracing::info!("Safe mode, not loading the project environment");
+        ProjectEnv {
+            dir: project_dir.clone(),
+            sources: Vec::new(),
+        }
+    } else {
+        cli::resolve_project_env(&project_dir)
+    };
+    for source in &project_env.sources {
+        tracing::debug!(
+            "Applying {} from {}: {}",
+            source.vars.len(),
+            source.source,
+            source.vars.join(", ")
+        );
+    }
+    let project_vars = project_env.vars();
+    *state.project_env.lock().unwrap() = Some(project_env);
+
+    // Mask everything secret the sidecar is given should it end up in its output
+    let redactor = log_state.redactor();
+    redactor.add_secret(password);
+    redactor.add_env(&project_vars);
+    redactor.add_env(&env);
+    #[cfg(unix)]
+    redactor.add_env(shell_env::login_env());
+
+    let mut args = vec!["serve".into(), "--port".into(), port.to_string().into()];
+    if let Some(level) = app.try_state::<Args>().and_then(|args| args.log_level) {
+        args.extend(["--log-level".into(), level.as_server_arg().into()]);
+    }
+    let (mut rx, child) = cli::create_command(app, &args)
+        .current_dir(&project_dir)
+        .envs(project_vars)
+        .envs(env)
         .env("OPENCODE_SERVER_PASSWORD", password)
         .spawn()
-        .expect("Failed to spawn opencode");
+        .map_err(|e| format!("Failed to spawn opencode: {e}"))?;
+
+    #[cfg(unix)]
+    pid_file::write(app, child.pid(), port);
+
+    lifecycle::transition(app, state, ServerPhase::Starting, |l| {
+        l.url = (port != 0).then(|| format!("http://127.0.0.1:{port}"));
+        l.pid = Some(child.pid());
+        l.local = true;
+    });
+
+    let (exit_tx, exit_rx) = watch::channel(None);
+    let (startup_tx, startup_rx) = oneshot::channel();
 
+    let pid = child.pid();
     tauri::async_runtime::spawn(async move {
+        let mut exit_tx = Some(exit_tx);
+        let mut startup_tx = Some(startup_tx);
+        let mut stdout = StreamCapture::new(LogStream::Stdout, pid, log_state.keep_ansi);
+        let mut stderr = StreamCapture::new(LogStream::Stderr, pid, log_state.keep_ansi);
+
         while let Some(event) = rx.recv().await {
+            let entries = match &event {
+                CommandEvent::Stdout(bytes) => stdout.push(bytes),
+                CommandEvent::Stderr(bytes) => stderr.push(bytes),
+                // Keep the last line of output even if it was not terminated
+                CommandEvent::Terminated(_) | CommandEvent::Error(_) => {
+                    stdout.finish().into_iter().chain(stderr.finish()).collect()
+                }
+                _ => Vec::new(),
+            };
+
+            for entry in entries {
+                if startup_tx.is_some()
+                    && let Some(startup) = parse_startup_line(&logs::strip_ansi(&entry.message))
+                    && let Some(tx) = startup_tx.take()
+                {
+                    let _ = tx.send(startup);
+                }
+
+                trace::echo_sidecar(&log_state.push(entry));
+            }
+
             match event {
-                CommandEvent::Stdout(line_bytes) => {
-                    let line = String::from_utf8_lossy(&line_bytes);
-                    print!("{line}");
-
-                    // Store log in shared state
-                    if let Ok(mut logs) = log_state_clone.0.lock() {
-                        logs.push_back(format!("[STDOUT] {}", line));
-                        // Keep only the last MAX_LOG_ENTRIES
-                        while logs.len() > MAX_LOG_ENTRIES {
-                            logs.pop_front();
-                        }
+                CommandEvent::Terminated(payload) => {
+                    tracing::info!(
+                        pid,
+                        "Sidecar terminated (code: {:?}, signal: {:?})",
+                        payload.code,
+                        payload.signal
+                    );
+                    if let Some(tx) = exit_tx.take() {
+                        let _ = tx.send(Some(SidecarExit {
+                            code: payload.code,
+                            signal: payload.signal,
+                            error: None,
+                        }));
                     }
                 }
-                CommandEvent::Stderr(line_bytes) => {
-                    let line = String::from_utf8_lossy(&line_bytes);
-                    eprint!("{line}");
-
-                    // Store log in shared state
-                    if let Ok(mut logs) = log_state_clone.0.lock() {
-                        logs.push_back(format!("[STDERR] {}", line));
-                        // Keep only the last MAX_LOG_ENTRIES
-                        while logs.len() > MAX_LOG_ENTRIES {
-                            logs.pop_front();
-                        }
+                CommandEvent::Error(error) => {
+                    tracing::error!(pid, "Sidecar error: {error}");
+                    if let Some(tx) = exit_tx.take() {
+                        let _ = tx.send(Some(SidecarExit {
+                            code: None,
+                            signal: None,
+                            error: Some(error),
+                        }));
                     }
                 }
                 _ => {}
@@ -194,7 +531,31 @@ fn spawn_sidecar(app: &AppHandle, port: u32, password: &str) -> CommandChild {
         }
     });
 
-    child
+    Ok((child, exit_rx, startup_rx))
+}
+
+/// Makes `child` the tracked sidecar, tying its lifetime to the app on Windows.
+fn register_child(app: &AppHandle, state: &ServerState, child: CommandChild, exit: ExitWatch) {
+    #[cfg(windows)]
+    app.state::<JobObjectState>().assign_pid(child.pid());
+
+    #[cfg(unix)]
+    app.state::<WatchdogState>().assign_pid(child.pid());
+
+    *state.exit.lock().unwrap() = Some(exit);
+    state.set_child(Some(child));
+}
+
+/// Drops the cleanup guard of a sidecar that has exited or been killed.
+fn release_child(app: &AppHandle, pid: u32) {
+    #[cfg(unix)]
+    {
+        app.state::<WatchdogState>().release(pid);
+        pid_file::remove(app, pid);
+    }
+
+    #[cfg(not(unix))]
+    let _ = (app, pid);
 }
 
 async fn check_server_health(url: &str, password: Option<&str>) -> bool {
@@ -220,22 +581,12 @@ async fn check_server_health(url: &str, password: Option<&str>) -> bool {
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    let updater_enabled = option_env!("TAURI_SIGNING_PRIVATE_KEY").is_some();
-
-    #[cfg(target_os = "macos")]
-    let _ = std::process::Command::new("killall")
-        .arg("opencode-cli")
-        .output();
-
+pub fn run(args: Args, display_backend: Option<String>) {
     let mut builder = tauri::Builder::default()
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
-            // Focus existing window when another instance is launched
-            if let Some(window) = app.get_webview_window("main") {
-                let _ = window.set_focus();
-                let _ = window.unminimize();
-            }
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            instance::forwarded(app, args, cwd);
         }))
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_window_state::Builder::new().build())
         .plugin(tauri_plugin_store::Builder::new().build())
@@ -251,100 +602,153 @@ pub fn run() {
             kill_sidecar,
             install_cli,
             ensure_server_ready,
+            restart_server,
+            connect_to_server,
+            get_server_status,
+            get_project_env,
+            project::get_project_dir,
+            project::set_project_dir,
+            project::pick_project_dir,
+            project::open_project_window,
+            logs::get_server_logs,
+            logs::open_log_folder,
+            diagnostics::export_diagnostics,
             get_default_server_url,
             set_default_server_url
         ])
         .setup(move |app| {
+            let _startup = tracing::info_span!("startup").entered();
             let app = app.handle().clone();
+            app.manage(args.clone());
+            app.manage(diagnostics::DisplayBackend(display_backend.clone()));
 
-            // Initialize log state
-            app.manage(LogState(Arc::new(Mutex::new(VecDeque::new()))));
+            // macOS delivers links to the running app instead of starting a new instance
+            {
+                let handle = app.clone();
+                app.deep_link().on_open_url(move |event| {
+                    for url in event.urls() {
+                        deep_link::handle(&handle, url.as_str());
+                    }
+                });
+            }
+
+            // Installs without a desktop file (AppImage, dev builds) register at runtime
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                tracing::warn!("Failed to register {}:// links: {e}", deep_link::SCHEME);
+            }
+
+            // Capture the login-shell environment while the window loads
+            #[cfg(unix)]
+            std::thread::spawn(shell_env::login_env);
+
+            // Persist logs under the app log directory, or keep them in memory only
+            let log_dir = app
+                .path()
+                .app_log_dir()
+                .inspect_err(|e| tracing::warn!("Failed to resolve the log directory: {e}"))
+                .ok();
+            app.manage(LogState::new(log_dir, logs::keep_ansi(&app)));
+            trace::attach(&app.state::<LogState>());
+            trace::apply_settings(&app, &args);
+            crash_reporter::init(&app);
+            logs::start_tail(&app);
 
             #[cfg(windows)]
             app.manage(JobObjectState::new());
 
-            let primary_monitor = app.primary_monitor().ok().flatten();
-            let size = primary_monitor
-                .map(|m| m.size().to_logical(m.scale_factor()))
-                .unwrap_or(LogicalSize::new(1920, 1080));
-
-            #[allow(unused_mut)]
-            let mut window_builder =
-                WebviewWindow::builder(&app, "main", WebviewUrl::App("/".into()))
-                    .title("OpenCode")
-                    .inner_size(size.width as f64, size.height as f64)
-                    .decorations(true)
-                    .zoom_hotkeys_enabled(true)
-                    .disable_drag_drop_handler()
-                    .initialization_script(format!(
-                        r#"
-                      window.__OPENCODE__ ??= {{}};
-                      window.__OPENCODE__.updaterEnabled = {updater_enabled};
-                    "#
-                    ));
-
-            #[cfg(target_os = "macos")]
+            #[cfg(unix)]
             {
-                window_builder = window_builder
-                    .title_bar_style(tauri::TitleBarStyle::Overlay)
-                    .hidden_title(true);
+                app.manage(WatchdogState::new());
+                pid_file::reap_orphans(&app);
             }
 
-            window_builder.build().expect("Failed to create window");
+            let config = tracing::info_span!("config").entered();
+            let server_state = ServerState::new();
+            let cwd = std::env::current_dir().unwrap_or_default();
+            // `std::env::args` panics on paths that are not valid UTF-8; the project
+            // path itself comes from the parsed `args`
+            let argv = std::env::args_os()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+            let launch = LaunchRequest::new(argv, cwd, args.clone());
+            // Linux and Windows pass the link that launched the app as its argument
+            let link = launch
+                .args
+                .iter()
+                .skip(1)
+                .find(|arg| deep_link::is_link(arg))
+                .cloned();
+            // A link's project only opens once the user confirms it, in `deep_link::handle`
+            *server_state.project_dir.lock().unwrap() = launch
+                .project
+                .or_else(|| (!args.safe_mode).then(|| project::stored(&app)).flatten());
+            drop(config);
+
+            app.manage(Servers::default());
+            app.state::<Servers>()
+                .insert(MAIN_WINDOW, server_state.clone(), None);
+
+            if !args.headless {
+                build_window(&app, MAIN_WINDOW, WebviewUrl::App("/".into()))
+                    .expect("Failed to create window");
+            }
 
-            let (tx, rx) = oneshot::channel();
-            app.manage(ServerState::new(None, rx));
+            crash_reporter::offer_reports(&app);
 
             {
                 let app = app.clone();
+                let state = server_state.clone();
+                let headless = args.headless;
+                let print_password = args.print_password;
                 tauri::async_runtime::spawn(async move {
-                    let mut custom_url = None;
-
-                    if let Some(url) = get_default_server_url(app.clone()).ok().flatten() {
-                        println!("Using desktop-specific custom URL: {url}");
-                        custom_url = Some(url);
-                    }
-
-                    if custom_url.is_none()
-                        && let Some(cli_config) = cli::get_config(&app).await
-                        && let Some(url) = get_server_url_from_config(&cli_config)
-                    {
-                        println!("Using custom server URL from config: {url}");
-                        custom_url = Some(url);
-                    }
-
-                    let res = match setup_server_connection(&app, custom_url).await {
-                        Ok((child, url)) => {
-                            #[cfg(windows)]
-                            if let Some(child) = &child {
-                                let job_state = app.state::<JobObjectState>();
-                                job_state.assign_pid(child.pid());
+                    let res = connect(&app, &state).await;
+                    if headless {
+                        match &res {
+                            // The URL is the program's output; the password only on request
+                            Ok(data) => {
+                                println!("Headless server ready at {}", data.url);
+                                match &data.password {
+                                    Some(password) if print_password => {
+                                        println!("Username: opencode, password: {password}");
+                                    }
+                                    Some(_) => tracing::info!(
+                                        "The server requires a password, pass --print-password to show it"
+                                    ),
+                                    None => {}
+                                }
                             }
-
-                            app.state::<ServerState>().set_child(child);
-
-                            Ok(url)
+                            Err(e) => tracing::error!("Headless server failed to start: {e}"),
                         }
-                        Err(e) => Err(e),
-                    };
-
-                    let _ = tx.send(res);
+                    }
+                    state.set_status(Some(res));
                 });
             }
 
+            tauri::async_runtime::spawn(health::monitor(app.clone(), server_state));
+
+            if let Some(link) = link {
+                deep_link::handle(&app, &link);
+            }
+
             {
                 let app = app.clone();
                 tauri::async_runtime::spawn(async move {
                     if let Err(e) = sync_cli(app) {
-                        eprintln!("Failed to sync CLI: {e}");
+                        tracing::error!("Failed to sync CLI: {e}");
                     }
                 });
             }
 
             Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let WindowEvent::Destroyed = event {
+                servers::window_destroyed(window.app_handle(), window.label());
+            }
         });
 
-    if updater_enabled {
+    if UPDATER_ENABLED {
         builder = builder.plugin(tauri_plugin_updater::Builder::new().build());
     }
 
@@ -353,9 +757,9 @@ pub fn run() {
         .expect("error while running tauri application")
         .run(|app, event| {
             if let RunEvent::Exit = event {
-                println!("Received Exit");
+                tracing::info!("Received Exit");
 
-                kill_sidecar(app.clone());
+                tauri::async_runtime::block_on(servers::stop_all(app));
             }
         });
 }
@@ -363,7 +767,7 @@ pub fn run() {
 fn get_server_url_from_config(config: &cli::Config) -> Option<String> {
     let server = config.server.as_ref()?;
     let port = server.port?;
-    println!("server.port found in OC config: {port}");
+    tracing::debug!("server.port found in OC config: {port}");
     let hostname = server.hostname.as_ref();
 
     Some(format!(
@@ -373,23 +777,109 @@ fn get_server_url_from_config(config: &cli::Config) -> Option<String> {
     ))
 }
 
+/// Builds a webview window showing `url`, sized to the primary monitor.
+fn build_window(app: &AppHandle, label: &str, url: WebviewUrl) -> tauri::Result<WebviewWindow> {
+    let primary_monitor = app.primary_monitor().ok().flatten();
+    let size = primary_monitor
+        .map(|m| m.size().to_logical(m.scale_factor()))
+        .unwrap_or(LogicalSize::new(1920, 1080));
+
+    #[allow(unused_mut)]
+    let mut window_builder = WebviewWindow::builder(app, label, url)
+        .title("OpenCode")
+        .inner_size(size.width as f64, size.height as f64)
+        .decorations(true)
+        .zoom_hotkeys_enabled(true)
+        .disable_drag_drop_handler()
+        .initialization_script(format!(
+            r#"
+              window.__OPENCODE__ ??= {{}};
+              window.__OPENCODE__.updaterEnabled = {UPDATER_ENABLED};
+            "#
+        ));
+
+    #[cfg(target_os = "macos")]
+    {
+        window_builder = window_builder
+            .title_bar_style(tauri::TitleBarStyle::Overlay)
+            .hidden_title(true);
+    }
+
+    window_builder.build()
+}
+
+/// Picks the server to use (`--server-url`, then desktop setting, then CLI config,
+/// then a local sidecar) and connects `state` to it.
+///
+/// Safe mode skips the stored setting and the CLI config.
+#[tracing::instrument(name = "server_selection", skip_all)]
+async fn connect(app: &AppHandle, state: &ServerState) -> Result<ServerReadyData, String> {
+    let mut custom_url = app
+        .try_state::<Args>()
+        .and_then(|args| args.server_url.clone());
+    let safe_mode = safe_mode(app);
+
+    if let Some(url) = &custom_url {
+        tracing::info!("Using server URL from the command line: {url}");
+        state.set_source(ServerSource::CommandLine);
+    }
+
+    if custom_url.is_none()
+        && !safe_mode
+        && let Some(url) = get_default_server_url(app.clone()).ok().flatten()
+    {
+        tracing::info!("Using desktop-specific custom URL: {url}");
+        state.set_source(ServerSource::Setting);
+        custom_url = Some(url);
+    }
+
+    if custom_url.is_none()
+        && !safe_mode
+        && let Some(cli_config) = cli::get_config(app).await
+        && let Some(url) = get_server_url_from_config(&cli_config)
+    {
+        tracing::info!("Using custom server URL from config: {url}");
+        state.set_source(ServerSource::CliConfig);
+        custom_url = Some(url);
+    }
+
+    let res = setup_server_connection(app, state, custom_url).await;
+
+    if let Err(e) = &res {
+        lifecycle::transition(app, state, ServerPhase::Stopped, |l| {
+            l.message = Some(e.clone());
+        });
+    }
+
+    res
+}
+
 async fn setup_server_connection(
     app: &AppHandle,
+    state: &ServerState,
     custom_url: Option<String>,
-) -> Result<(Option<CommandChild>, ServerReadyData), String> {
+) -> Result<ServerReadyData, String> {
     if let Some(url) = custom_url {
         loop {
+            lifecycle::transition(app, state, ServerPhase::Starting, |l| {
+                l.url = Some(url.clone());
+                l.pid = None;
+                l.local = false;
+            });
+
             if check_server_health(&url, None).await {
-                println!("Connected to custom server: {}", url);
-                return Ok((
-                    None,
-                    ServerReadyData {
-                        url: url.clone(),
-                        password: None,
-                    },
-                ));
+                tracing::info!("Connected to custom server: {}", url);
+                lifecycle::transition(app, state, ServerPhase::Ready, |_| {});
+                return Ok(ServerReadyData {
+                    url: url.clone(),
+                    password: None,
+                });
             }
 
+            lifecycle::transition(app, state, ServerPhase::Unhealthy, |l| {
+                l.message = Some("Could not connect to configured server".to_string());
+            });
+
             const RETRY: &str = "Retry";
 
             let res = app.dialog()
@@ -409,55 +899,206 @@ async fn setup_server_connection(
         }
     }
 
-    let local_port = get_sidecar_port();
-    let local_url = format!("http://127.0.0.1:{local_port}");
-
-    if !check_server_health(&local_url, None).await {
-        let password = uuid::Uuid::new_v4().to_string();
-
-        match spawn_local_server(app, local_port, &password).await {
-            Ok(child) => Ok((
-                Some(child),
-                ServerReadyData {
-                    url: local_url,
-                    password: Some(password),
-                },
-            )),
-            Err(err) => Err(err),
-        }
-    } else {
-        Ok((
-            None,
-            ServerReadyData {
-                url: local_url,
+    let pinned_port = get_sidecar_port(app);
+
+    if let Some(port) = pinned_port {
+        let url = format!("http://127.0.0.1:{port}");
+
+        if check_server_health(&url, None).await {
+            lifecycle::transition(app, state, ServerPhase::Ready, |l| {
+                l.url = Some(url.clone());
+                l.pid = None;
+                l.local = false;
+            });
+            state.set_source(ServerSource::PinnedPort);
+
+            return Ok(ServerReadyData {
+                url,
                 password: None,
-            },
-        ))
+            });
+        }
+    }
+
+    let password = uuid::Uuid::new_v4().to_string();
+
+    let server = spawn_local_server(app, state, pinned_port, &password).await?;
+    register_child(app, state, server.child, server.exit.clone());
+    state.set_source(ServerSource::Sidecar);
+
+    tauri::async_runtime::spawn(supervisor::supervise(
+        app.clone(),
+        state.clone(),
+        server.port,
+        password.clone(),
+        server.exit,
+        state.generation(),
+    ));
+
+    Ok(ServerReadyData {
+        url: format!("http://127.0.0.1:{}", server.port),
+        password: Some(password),
+    })
+}
+
+/// Attempts to start the sidecar before giving up on a port that stays in use.
+const MAX_SPAWN_ATTEMPTS: usize = 3;
+
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const STARTUP_TIMEOUT_KEY: &str = "sidecarStartupTimeoutMs";
+const STARTUP_TIMEOUT_ENV: &str = "OPENCODE_STARTUP_TIMEOUT_MS";
+
+/// A healthy sidecar together with the port it actually bound.
+struct LocalServer {
+    child: CommandChild,
+    exit: ExitWatch,
+    port: u32,
+}
+
+/// Polls `/global/health` with capped exponential backoff until `deadline`.
+#[tracing::instrument(name = "health", skip_all, fields(url = %url))]
+async fn wait_until_healthy(url: &str, password: &str, deadline: Instant) -> bool {
+    let mut delay = Duration::from_millis(25);
+
+    loop {
+        if check_server_health(url, Some(password)).await {
+            return true;
+        }
+        if Instant::now() + delay > deadline {
+            return false;
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(Duration::from_secs(1));
     }
 }
 
+/// Tears down a sidecar that failed to start and builds the error shown to the user.
+async fn startup_failed(
+    app: &AppHandle,
+    state: &ServerState,
+    child: CommandChild,
+    reason: String,
+) -> String {
+    lifecycle::transition(app, state, ServerPhase::Unhealthy, |l| {
+        l.message = Some(reason.clone());
+    });
+
+    let pid = child.pid();
+    let _ = child.kill();
+    release_child(app, pid);
+
+    format!(
+        "Failed to spawn OpenCode Server: {reason}. Logs:\n{}",
+        get_logs(app).unwrap_or_default()
+    )
+}
+
+/// Starts the sidecar on `port`, or on a port of its choosing when `None`.
+///
+/// Readiness is signalled by the sidecar's "listening" line, confirmed by a health
+/// check. A port that is still in use is retried a few times before falling back
+/// to an ephemeral one, so callers must use the returned port.
+#[tracing::instrument(name = "spawn", skip_all, fields(port = ?port))]
 async fn spawn_local_server(
     app: &AppHandle,
-    port: u32,
+    state: &ServerState,
+    port: Option<u32>,
     password: &str,
-) -> Result<CommandChild, String> {
-    let child = spawn_sidecar(app, port, password);
-    let url = format!("http://127.0.0.1:{port}");
-
+) -> Result<LocalServer, String> {
+    let timeout = get_duration_setting(app, STARTUP_TIMEOUT_KEY, STARTUP_TIMEOUT_ENV)
+        .unwrap_or(DEFAULT_STARTUP_TIMEOUT);
     let timestamp = Instant::now();
-    loop {
-        if timestamp.elapsed() > Duration::from_secs(30) {
-            break Err(format!(
-                "Failed to spawn OpenCode Server. Logs:\n{}",
-                get_logs(app.clone()).await.unwrap()
-            ));
+    let deadline = timestamp + timeout;
+    let mut attempt = 0;
+
+    let (child, exit, port) = loop {
+        attempt += 1;
+        let requested = match port {
+            Some(port) if attempt < MAX_SPAWN_ATTEMPTS => port,
+            _ => 0,
+        };
+
+        let (child, exit, startup) = match spawn_sidecar(app, state, requested, password) {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                tracing::error!("{e}");
+                lifecycle::transition(app, state, ServerPhase::Unhealthy, |l| {
+                    l.message = Some(e.clone());
+                });
+                return Err(e);
+            }
+        };
+        let pid = child.pid();
+
+        let startup = tokio::time::timeout_at(deadline.into(), startup).await;
+        match startup {
+            Ok(Ok(SidecarStartup::Listening(port))) => break (child, exit, port),
+            Ok(Ok(SidecarStartup::AddrInUse)) if attempt < MAX_SPAWN_ATTEMPTS => {
+                tracing::warn!("Port {requested} is in use, retrying (attempt {attempt})");
+                let _ = child.kill();
+                release_child(app, pid);
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Ok(Ok(SidecarStartup::AddrInUse)) => {
+                return Err(startup_failed(app, state, child, "Port is in use".to_string()).await);
+            }
+            // The startup channel closes once the sidecar has exited
+            Ok(Err(_)) => {
+                let status = wait_exit(&mut exit.clone()).await;
+                let reason = format!("Server exited during startup ({status})");
+                return Err(startup_failed(app, state, child, reason).await);
+            }
+            Err(_) => {
+                let reason = format!("Server did not start listening within {timeout:?}");
+                return Err(startup_failed(app, state, child, reason).await);
+            }
         }
+    };
+
+    #[cfg(unix)]
+    pid_file::write(app, child.pid(), port);
 
-        tokio::time::sleep(Duration::from_millis(10)).await;
+    let url = format!("http://127.0.0.1:{port}");
+    let mut exited = exit.clone();
 
-        if check_server_health(&url, Some(password)).await {
-            println!("Server ready after {:?}", timestamp.elapsed());
-            break Ok(child);
+    let healthy = tokio::select! {
+        healthy = wait_until_healthy(&url, password, deadline) => healthy,
+        status = wait_exit(&mut exited) => {
+            let reason = format!("Server exited during startup ({status})");
+            return Err(startup_failed(app, state, child, reason).await);
         }
+    };
+
+    if !healthy {
+        let reason = format!("Server did not become healthy within {timeout:?}");
+        return Err(startup_failed(app, state, child, reason).await);
+    }
+
+    tracing::info!("Server ready after {:?}", timestamp.elapsed());
+    lifecycle::transition(app, state, ServerPhase::Ready, |l| {
+        l.url = Some(url.clone());
+    });
+
+    Ok(LocalServer { child, exit, port })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_startup_line() {
+        assert_eq!(
+            parse_startup_line("opencode server listening on http://127.0.0.1:4096\n"),
+            Some(SidecarStartup::Listening(4096))
+        );
+        assert_eq!(
+            parse_startup_line("error: Failed to start server on port 4096"),
+            Some(SidecarStartup::AddrInUse)
+        );
+        assert_eq!(
+            parse_startup_line("Warning: OPENCODE_SERVER_PASSWORD is not set"),
+            None
+        );
     }
 }
@@ -0,0 +1,181 @@
+//! Server lifecycle tracking.
+//!
+//! Every transition of a server is recorded in its `ServerState` and emitted as
+//! a `server://<phase>` event to the windows it backs, so the frontend can
+//! render a live status indicator. `get_server_status` returns the same payload
+//! on demand for windows that subscribe late.
+
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, WebviewWindow};
+
+use crate::ServerState;
+use crate::servers;
+use crate::supervisor::{CrashReport, SidecarExit};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerPhase {
+    Starting,
+    Ready,
+    Unhealthy,
+    Crashed,
+    Restarting,
+    Stopped,
+}
+
+impl ServerPhase {
+    pub fn event(self) -> &'static str {
+        match self {
+            Self::Starting => "server://starting",
+            Self::Ready => "server://ready",
+            Self::Unhealthy => "server://unhealthy",
+            Self::Crashed => "server://crashed",
+            Self::Restarting => "server://restarting",
+            Self::Stopped => "server://stopped",
+        }
+    }
+}
+
+/// Mutable lifecycle record kept in `ServerState`.
+pub struct Lifecycle {
+    pub phase: ServerPhase,
+    pub url: Option<String>,
+    pub pid: Option<u32>,
+    /// Whether the server is a sidecar owned by this app.
+    pub local: bool,
+    pub exit: Option<SidecarExit>,
+    pub message: Option<String>,
+    pub attempt: Option<usize>,
+    pub retry_in: Option<Duration>,
+    /// Round-trip time of the last successful health check.
+    pub latency: Option<Duration>,
+    /// Health checks failed in a row since the last successful one.
+    pub failures: u32,
+    ready_at: Option<Instant>,
+    uptime: Option<Duration>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            phase: ServerPhase::Starting,
+            url: None,
+            pid: None,
+            local: false,
+            exit: None,
+            message: None,
+            attempt: None,
+            retry_in: None,
+            latency: None,
+            failures: 0,
+            ready_at: None,
+            uptime: None,
+        }
+    }
+}
+
+impl Lifecycle {
+    fn snapshot(&self) -> ServerStatus {
+        let uptime = match self.phase {
+            ServerPhase::Ready | ServerPhase::Unhealthy => self.ready_at.map(|t| t.elapsed()),
+            _ => self.uptime,
+        };
+
+        ServerStatus {
+            phase: self.phase,
+            url: self.url.clone(),
+            pid: self.pid,
+            local: self.local,
+            uptime_ms: uptime.map(|d| d.as_millis() as u64),
+            exit: self.exit.clone(),
+            message: self.message.clone(),
+            attempt: self.attempt,
+            retry_in_ms: self.retry_in.map(|d| d.as_millis() as u64),
+            latency_ms: self.latency.map(|d| d.as_millis() as u64),
+            consecutive_failures: self.failures,
+            last_crash: None,
+        }
+    }
+}
+
+/// Payload of every `server://*` event and of `get_server_status`.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    pub phase: ServerPhase,
+    pub url: Option<String>,
+    pub pid: Option<u32>,
+    pub local: bool,
+    pub uptime_ms: Option<u64>,
+    pub exit: Option<SidecarExit>,
+    pub message: Option<String>,
+    pub attempt: Option<usize>,
+    pub retry_in_ms: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_crash: Option<CrashReport>,
+}
+
+/// Moves the server to `phase`, applies `update` and notifies its windows.
+///
+/// Per-transition fields (`exit`, `message`, `attempt`, `retry_in`) are reset
+/// first so stale details never leak into the next event.
+pub fn transition(
+    app: &AppHandle,
+    state: &ServerState,
+    phase: ServerPhase,
+    update: impl FnOnce(&mut Lifecycle),
+) {
+    let status = {
+        let mut lifecycle = state.lifecycle.lock().unwrap();
+
+        if lifecycle.phase != phase {
+            match phase {
+                ServerPhase::Starting => {
+                    lifecycle.latency = None;
+                    lifecycle.failures = 0;
+                }
+                ServerPhase::Ready if lifecycle.phase != ServerPhase::Unhealthy => {
+                    lifecycle.ready_at = Some(Instant::now());
+                    lifecycle.uptime = None;
+                }
+                ServerPhase::Crashed | ServerPhase::Stopped => {
+                    lifecycle.uptime = lifecycle.ready_at.take().map(|t| t.elapsed());
+                }
+                _ => {}
+            }
+        }
+
+        lifecycle.phase = phase;
+        lifecycle.exit = None;
+        lifecycle.message = None;
+        lifecycle.attempt = None;
+        lifecycle.retry_in = None;
+        update(&mut lifecycle);
+
+        lifecycle.snapshot()
+    };
+
+    tracing::info!(
+        pid = status.pid,
+        url = status.url.as_deref(),
+        detail = status.message.as_deref(),
+        "Server {phase:?}"
+    );
+
+    servers::emit(app, state, phase.event(), &status);
+}
+
+/// The current status of `state`, including its last crash.
+pub fn status_of(state: &ServerState) -> ServerStatus {
+    let mut status = state.lifecycle.lock().unwrap().snapshot();
+    status.last_crash = state.crash.lock().unwrap().clone();
+    status
+}
+
+#[tauri::command]
+pub fn get_server_status(window: WebviewWindow) -> Result<ServerStatus, String> {
+    let state = servers::state_of(&window)?;
+    Ok(status_of(&state))
+}
@@ -0,0 +1,838 @@
+//! Log capture for the sidecar and the desktop app.
+//!
+//! The shell plugin hands over sidecar output in arbitrary chunks, so each stream
+//! is buffered into complete lines before being decoded, which keeps multi-byte
+//! UTF-8 sequences intact across chunk boundaries. Every line becomes a
+//! `LogEntry` tagged with a timestamp, its stream and the level the server
+//! printed, with ANSI colour codes stripped unless configured otherwise.
+//!
+//! Secrets are masked by `redact::Redactor` before an entry goes anywhere.
+//! Entries are kept in memory for crash reports and written to rotating files
+//! under the app log directory (`sidecar.log` and `desktop.log`), so the evidence
+//! for a bug report survives restarts. `open_log_folder` reveals them.
+//!
+//! The webview reads the in-memory entries through `get_server_logs` and tails
+//! new ones through `server://log`, which batches entries so a chatty server
+//! cannot flood the IPC bridge.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_opener::OpenerExt;
+use tauri_plugin_store::StoreExt;
+
+use crate::args::LogLevel;
+use crate::redact::Redactor;
+
+/// Entries kept in memory.
+const MAX_LOG_ENTRIES: usize = 1000;
+
+/// Lines longer than this are split rather than buffered indefinitely.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Size at which a log file is rotated.
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+/// Rotated files kept next to the current one (`sidecar.log.1` and so on).
+const MAX_ROTATED_FILES: usize = 5;
+/// Rotated files older than this are deleted.
+const MAX_FILE_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub const SIDECAR_LOG: &str = "sidecar.log";
+pub const DESKTOP_LOG: &str = "desktop.log";
+
+/// Entries returned by `get_server_logs` when no limit is given, and at most.
+const DEFAULT_PAGE_SIZE: usize = 200;
+const MAX_PAGE_SIZE: usize = MAX_LOG_ENTRIES;
+
+pub const LOG_EVENT: &str = "server://log";
+/// Minimum time between two `server://log` events.
+const TAIL_INTERVAL: Duration = Duration::from_millis(250);
+/// Entries held for the next `server://log` event; more are dropped and counted.
+const MAX_TAIL_BATCH: usize = 500;
+
+const KEEP_ANSI_KEY: &str = "keepLogColors";
+const KEEP_ANSI_ENV: &str = "OPENCODE_LOG_KEEP_ANSI";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Messages of the desktop app itself.
+    Desktop,
+}
+
+impl LogStream {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Stdout => "STDOUT",
+            Self::Stderr => "STDERR",
+            Self::Desktop => "DESKTOP",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// Position in the log, increasing by one per entry; the pagination cursor.
+    pub id: u64,
+    /// Milliseconds since the Unix epoch at which the line was received.
+    pub timestamp_ms: u64,
+    pub stream: LogStream,
+    pub level: Option<LogLevel>,
+    /// Sidecar the line came from.
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+impl LogEntry {
+    pub fn new(stream: LogStream, pid: Option<u32>, message: String) -> Self {
+        Self {
+            id: 0,
+            timestamp_ms: now_ms(),
+            stream,
+            level: parse_level(&strip_ansi(&message)),
+            pid,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [{}",
+            format_timestamp(self.timestamp_ms),
+            self.stream.label()
+        )?;
+        if let Some(pid) = self.pid {
+            write!(f, " {pid}")?;
+        }
+        write!(f, "] {}", self.message)
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Formats `ms` since the Unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        ms % 1000
+    )
+}
+
+/// Removes ANSI escape sequences (colours, cursor movement, titles) from `line`.
+pub fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Two-character sequences such as ESC ( B
+            Some('(' | ')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+/// Level of a server log line such as `WARN  2025-01-01T00:00:00 +3ms ...`.
+pub fn parse_level(line: &str) -> Option<LogLevel> {
+    let word = line.split_whitespace().next()?;
+    let word = word.trim_matches(|c: char| matches!(c, '[' | ']' | ':'));
+
+    match word.to_ascii_uppercase().as_str() {
+        "DEBUG" | "TRACE" => Some(LogLevel::Debug),
+        "INFO" => Some(LogLevel::Info),
+        "WARN" | "WARNING" => Some(LogLevel::Warn),
+        "ERROR" | "FATAL" => Some(LogLevel::Error),
+        _ => None,
+    }
+}
+
+/// Reassembles complete lines from the chunks of one output stream.
+#[derive(Default)]
+pub struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Adds `chunk` and returns the lines it completed, without line endings.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(decode(&line[..end]));
+        }
+
+        while self.pending.len() > MAX_LINE_LEN {
+            // Split on a character boundary so no UTF-8 sequence is torn apart
+            let mut cut = MAX_LINE_LEN;
+            while cut > 0 && self.pending[cut] & 0xC0 == 0x80 {
+                cut -= 1;
+            }
+            let line: Vec<u8> = self.pending.drain(..cut).collect();
+            lines.push(decode(&line));
+        }
+
+        lines
+    }
+
+    /// The incomplete last line, once the stream has ended.
+    pub fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let line = std::mem::take(&mut self.pending);
+        Some(decode(&line))
+    }
+}
+
+fn decode(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).into_owned()
+}
+
+/// A log file that is rotated once it reaches `MAX_FILE_SIZE`.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        prune(&path);
+
+        Ok(Self { path, file, size })
+    }
+
+    fn rotated(path: &Path, n: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        for n in (1..MAX_ROTATED_FILES).rev() {
+            let from = Self::rotated(&self.path, n);
+            if from.exists() {
+                std::fs::rename(&from, Self::rotated(&self.path, n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, Self::rotated(&self.path, 1))?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        prune(&self.path);
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) {
+        let len = line.len() as u64 + 1;
+        if self.size > 0
+            && self.size + len > MAX_FILE_SIZE
+            && let Err(e) = self.rotate()
+        {
+            // Called with the file lock held, so logging through tracing would deadlock.
+            eprintln!("Failed to rotate {}: {e}", self.path.display());
+        }
+
+        if writeln!(self.file, "{line}").is_ok() {
+            self.size += len;
+        }
+    }
+}
+
+/// Deletes rotated copies of `path` that are too old or too many.
+fn prune(path: &Path) {
+    let now = SystemTime::now();
+
+    for n in 1..=MAX_ROTATED_FILES + 1 {
+        let rotated = RotatingFile::rotated(path, n);
+        let Ok(modified) = rotated.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+
+        let expired = now
+            .duration_since(modified)
+            .is_ok_and(|age| age > MAX_FILE_AGE);
+        if n > MAX_ROTATED_FILES || expired {
+            let _ = std::fs::remove_file(&rotated);
+        }
+    }
+}
+
+/// Recent log entries, plus the files they are persisted to.
+#[derive(Clone)]
+pub struct LogState {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+    tail: Arc<Tail>,
+    redactor: Arc<Redactor>,
+    sidecar_file: Option<Arc<Mutex<RotatingFile>>>,
+    desktop_file: Option<Arc<Mutex<RotatingFile>>>,
+    dir: Option<PathBuf>,
+    /// Whether ANSI codes are kept in sidecar output.
+    pub keep_ansi: bool,
+}
+
+impl LogState {
+    /// Log state writing to files under `dir`, or to memory only if it is `None`.
+    pub fn new(dir: Option<PathBuf>, keep_ansi: bool) -> Self {
+        let open = |name: &str| {
+            let path = dir.as_ref()?.join(name);
+            RotatingFile::open(path)
+                .inspect_err(|e| tracing::warn!("Failed to open log file {name}: {e}"))
+                .ok()
+                .map(|file| Arc::new(Mutex::new(file)))
+        };
+
+        // Keys the app was started with are passed on to the sidecar
+        let redactor = Redactor::default();
+        redactor.add_env(std::env::vars());
+
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+            tail: Arc::default(),
+            redactor: Arc::new(redactor),
+            sidecar_file: open(SIDECAR_LOG),
+            desktop_file: open(DESKTOP_LOG),
+            dir,
+            keep_ansi,
+        }
+    }
+
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Masks secrets in `entry` and stores it, returning the stored entry.
+    pub fn push(&self, mut entry: LogEntry) -> LogEntry {
+        if let Cow::Owned(message) = self.redactor.redact(&entry.message) {
+            entry.message = message;
+        }
+
+        let file = match entry.stream {
+            LogStream::Desktop => &self.desktop_file,
+            LogStream::Stdout | LogStream::Stderr => &self.sidecar_file,
+        };
+        if let Some(file) = file {
+            file.lock().unwrap().write_line(&entry.to_string());
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entry.id = entries.back().map_or(0, |e| e.id) + 1;
+        self.tail.push(&entry);
+        entries.push_back(entry.clone());
+        while entries.len() > MAX_LOG_ENTRIES {
+            entries.pop_front();
+        }
+        entry
+    }
+
+    pub fn query(&self, query: &LogQuery) -> LogPage {
+        query.apply(&self.entries.lock().unwrap())
+    }
+
+    /// The last `count` entries, oldest first.
+    pub fn recent(&self, count: usize) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let skip = entries.len().saturating_sub(count);
+        entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// Like `recent`, but `None` instead of waiting if the entries are locked.
+    pub fn try_recent(&self, count: usize) -> Option<Vec<LogEntry>> {
+        let entries = match self.entries.try_lock() {
+            Ok(entries) => entries,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        let skip = entries.len().saturating_sub(count);
+        Some(entries.iter().skip(skip).cloned().collect())
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+}
+
+/// Filters of `get_server_logs`. Every field is optional.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogQuery {
+    /// Streams to include, all if empty.
+    pub streams: Vec<LogStream>,
+    /// Least severe level to include. Lines without a level are left out.
+    pub level: Option<LogLevel>,
+    pub pid: Option<u32>,
+    /// Inclusive time range, in milliseconds since the Unix epoch.
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    /// Case-insensitive substring of the message.
+    pub contains: Option<String>,
+    /// Only entries older than this id; `nextCursor` of the previous page.
+    pub cursor: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    /// Matching entries, oldest first.
+    pub entries: Vec<LogEntry>,
+    /// Cursor for the next, older page, if there are more matches.
+    pub next_cursor: Option<u64>,
+}
+
+impl LogQuery {
+    fn matches(&self, entry: &LogEntry, contains: Option<&str>) -> bool {
+        (self.streams.is_empty() || self.streams.contains(&entry.stream))
+            && self
+                .level
+                .is_none_or(|min| entry.level.is_some_and(|level| level >= min))
+            && self.pid.is_none_or(|pid| entry.pid == Some(pid))
+            && self.since_ms.is_none_or(|t| entry.timestamp_ms >= t)
+            && self.until_ms.is_none_or(|t| entry.timestamp_ms <= t)
+            && self.cursor.is_none_or(|id| entry.id < id)
+            && contains.is_none_or(|s| entry.message.to_lowercase().contains(s))
+    }
+
+    /// The newest page of `entries` matching the query.
+    fn apply(&self, entries: &VecDeque<LogEntry>) -> LogPage {
+        let limit = self
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let contains = self
+            .contains
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase);
+
+        let mut matching = entries
+            .iter()
+            .rev()
+            .filter(|e| self.matches(e, contains.as_deref()));
+
+        let mut page: Vec<LogEntry> = matching.by_ref().take(limit).cloned().collect();
+        page.reverse();
+
+        let more = matching.next().is_some();
+        LogPage {
+            next_cursor: more.then(|| page[0].id),
+            entries: page,
+        }
+    }
+}
+
+/// Entries waiting for the next `server://log` event.
+#[derive(Default)]
+struct Tail {
+    pending: Mutex<TailBatch>,
+    notify: tokio::sync::Notify,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TailBatch {
+    pub entries: Vec<LogEntry>,
+    /// Entries left out because the batch was full.
+    pub dropped: usize,
+}
+
+impl Tail {
+    fn push(&self, entry: &LogEntry) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.entries.len() < MAX_TAIL_BATCH {
+            pending.entries.push(entry.clone());
+        } else {
+            pending.dropped += 1;
+        }
+        self.notify.notify_one();
+    }
+
+    fn take(&self) -> TailBatch {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+/// Emits new entries as `server://log`, at most once per `TAIL_INTERVAL`.
+pub fn start_tail(app: &AppHandle) {
+    let app = app.clone();
+    let tail = app.state::<LogState>().tail.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tail.notify.notified().await;
+
+            let batch = tail.take();
+            if batch.dropped > 0 {
+                tracing::warn!("Dropped {} log entries from {LOG_EVENT}", batch.dropped);
+            }
+            if let Err(e) = app.emit(LOG_EVENT, batch) {
+                tracing::warn!("Failed to emit {LOG_EVENT}: {e}");
+            }
+
+            tokio::time::sleep(TAIL_INTERVAL).await;
+        }
+    });
+}
+
+/// Turns one output stream of a sidecar into log entries.
+pub struct StreamCapture {
+    stream: LogStream,
+    pid: u32,
+    keep_ansi: bool,
+    buffer: LineBuffer,
+}
+
+impl StreamCapture {
+    pub fn new(stream: LogStream, pid: u32, keep_ansi: bool) -> Self {
+        Self {
+            stream,
+            pid,
+            keep_ansi,
+            buffer: LineBuffer::default(),
+        }
+    }
+
+    fn entry(&self, line: String) -> LogEntry {
+        let message = if self.keep_ansi {
+            line
+        } else {
+            strip_ansi(&line).into_owned()
+        };
+        LogEntry::new(self.stream, Some(self.pid), message)
+    }
+
+    /// Entries for the lines `chunk` completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<LogEntry> {
+        let lines = self.buffer.push(chunk);
+        lines.into_iter().map(|line| self.entry(line)).collect()
+    }
+
+    /// Entry for the unterminated last line, once the stream has ended.
+    pub fn finish(&mut self) -> Option<LogEntry> {
+        let line = self.buffer.finish()?;
+        Some(self.entry(line))
+    }
+}
+
+/// Whether sidecar output keeps its ANSI codes, from `OPENCODE_LOG_KEEP_ANSI` or
+/// the settings store.
+pub fn keep_ansi(app: &AppHandle) -> bool {
+    let from_env = std::env::var(KEEP_ANSI_ENV)
+        .ok()
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"));
+    let from_store = || {
+        if crate::safe_mode(app) {
+            return None;
+        }
+        app.store(crate::settings_store(app))
+            .ok()
+            .and_then(|store| store.get(KEEP_ANSI_KEY))
+            .and_then(|v| v.as_bool())
+    };
+
+    from_env.or_else(from_store).unwrap_or(false)
+}
+
+/// Entries in memory matching `query`, newest page first.
+#[tauri::command]
+pub fn get_server_logs(app: AppHandle, query: Option<LogQuery>) -> Result<LogPage, String> {
+    let log_state = app.try_state::<LogState>().ok_or("Log state not found")?;
+    Ok(log_state.query(&query.unwrap_or_default()))
+}
+
+/// Opens the folder holding the log files in the system file manager.
+#[tauri::command]
+pub fn open_log_folder(app: AppHandle) -> Result<(), String> {
+    let dir = app
+        .state::<LogState>()
+        .dir()
+        .map(Path::to_path_buf)
+        .ok_or("Log directory is not available")?;
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| format!("Failed to open {}: {e}", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_line_buffer_reassembles_lines() {
+        let mut buffer = LineBuffer::default();
+        assert!(buffer.push(b"INFO  partial").is_empty());
+        assert_eq!(
+            buffer.push(b" line\r\nsecond\nthi"),
+            ["INFO  partial line", "second"]
+        );
+        assert_eq!(buffer.finish().as_deref(), Some("thi"));
+        assert_eq!(buffer.finish(), None);
+    }
+
+    #[test]
+    fn test_line_buffer_keeps_utf8_across_chunks() {
+        let bytes = "café 🚀\n".as_bytes();
+        let mut buffer = LineBuffer::default();
+
+        let mut lines = Vec::new();
+        for byte in bytes {
+            lines.extend(buffer.push(&[*byte]));
+        }
+        assert_eq!(lines, ["café 🚀"]);
+    }
+
+    #[test]
+    fn test_line_buffer_splits_long_lines_on_char_boundaries() {
+        let long = "é".repeat(MAX_LINE_LEN);
+        let mut buffer = LineBuffer::default();
+
+        let lines = buffer.push(long.as_bytes());
+        assert!(lines.iter().all(|l| !l.contains('\u{FFFD}')));
+        assert_eq!(lines.concat() + &buffer.finish().unwrap(), long);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(
+            strip_ansi("\x1b[31;1mERROR\x1b[0m failed \x1b]0;title\x07done\x1b(B"),
+            "ERROR failed done"
+        );
+        assert_eq!(strip_ansi("\x1b]8;;http://x\x1b\\link"), "link");
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(
+            parse_level("WARN  2025-01-01T00:00:00 +3ms service=server slow"),
+            Some(LogLevel::Warn)
+        );
+        assert_eq!(parse_level("error: Failed to start"), Some(LogLevel::Error));
+        assert_eq!(parse_level("[debug] hello"), Some(LogLevel::Debug));
+        assert_eq!(parse_level("opencode server listening"), None);
+        assert_eq!(parse_level(""), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(951_782_400_123),
+            "2000-02-29T00:00:00.123Z"
+        );
+        assert_eq!(
+            format_timestamp(1_792_229_696_007),
+            "2026-10-17T09:34:56.007Z"
+        );
+    }
+
+    #[test]
+    fn test_query_filters_and_pages() {
+        let log_state = LogState::new(None, false);
+        for (stream, message) in [
+            (LogStream::Stderr, "INFO  starting"),
+            (LogStream::Stderr, "WARN  slow request"),
+            (LogStream::Stdout, "opencode server listening"),
+            (LogStream::Stderr, "ERROR request failed"),
+            (LogStream::Desktop, "Server Ready"),
+            (LogStream::Stderr, "ERROR Request timed out"),
+        ] {
+            log_state.push(LogEntry::new(stream, Some(7), message.to_string()));
+        }
+
+        let messages = |page: &LogPage| -> Vec<String> {
+            page.entries.iter().map(|e| e.message.clone()).collect()
+        };
+
+        let page = log_state.query(&LogQuery {
+            level: Some(LogLevel::Warn),
+            ..Default::default()
+        });
+        assert_eq!(
+            messages(&page),
+            [
+                "WARN  slow request",
+                "ERROR request failed",
+                "ERROR Request timed out"
+            ]
+        );
+        assert_eq!(page.next_cursor, None);
+
+        let page = log_state.query(&LogQuery {
+            streams: vec![LogStream::Stderr],
+            contains: Some("REQUEST".to_string()),
+            limit: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            messages(&page),
+            ["ERROR request failed", "ERROR Request timed out"]
+        );
+        assert_eq!(page.next_cursor, Some(4));
+
+        let page = log_state.query(&LogQuery {
+            streams: vec![LogStream::Stderr],
+            contains: Some("REQUEST".to_string()),
+            limit: Some(2),
+            cursor: page.next_cursor,
+            ..Default::default()
+        });
+        assert_eq!(messages(&page), ["WARN  slow request"]);
+        assert_eq!(page.next_cursor, None);
+
+        let all = log_state.query(&LogQuery::default());
+        let since = all.entries[4].timestamp_ms;
+        assert!(
+            log_state
+                .query(&LogQuery {
+                    since_ms: Some(since),
+                    ..Default::default()
+                })
+                .entries
+                .iter()
+                .all(|e| e.timestamp_ms >= since)
+        );
+        assert!(
+            log_state
+                .query(&LogQuery {
+                    pid: Some(8),
+                    ..Default::default()
+                })
+                .entries
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_push_redacts_secrets() {
+        let log_state = LogState::new(None, false);
+        log_state.redactor().add_secret("generated-password-value");
+
+        let entry = LogEntry::new(
+            LogStream::Stderr,
+            None,
+            "ERROR auth failed for generated-password-value".to_string(),
+        );
+        assert_eq!(
+            log_state.push(entry).message,
+            "ERROR auth failed for [REDACTED]"
+        );
+        assert_eq!(
+            log_state.recent(1)[0].message,
+            "ERROR auth failed for [REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_tail_caps_batches() {
+        let tail = Tail::default();
+        let entry = LogEntry::new(LogStream::Stdout, None, "line".to_string());
+        for _ in 0..MAX_TAIL_BATCH + 3 {
+            tail.push(&entry);
+        }
+
+        let batch = tail.take();
+        assert_eq!(batch.entries.len(), MAX_TAIL_BATCH);
+        assert_eq!(batch.dropped, 3);
+        assert!(tail.take().entries.is_empty());
+    }
+
+    #[test]
+    fn test_rotating_file_rotates_and_caps_copies() {
+        let dir = TempDir::new("logs");
+        let path = dir.join(SIDECAR_LOG);
+        let line = "x".repeat(MAX_FILE_SIZE as usize / 2);
+
+        let mut file = RotatingFile::open(path.clone()).unwrap();
+        for _ in 0..(MAX_ROTATED_FILES + 3) * 2 {
+            file.write_line(&line);
+        }
+
+        assert!(path.exists());
+        assert!(RotatingFile::rotated(&path, MAX_ROTATED_FILES).exists());
+        assert!(!RotatingFile::rotated(&path, MAX_ROTATED_FILES + 1).exists());
+        assert!(std::fs::metadata(&path).unwrap().len() <= MAX_FILE_SIZE);
+    }
+}
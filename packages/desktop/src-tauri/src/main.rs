@@ -1,6 +1,9 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use clap::Parser;
+use opencode_lib::Args;
+
 // borrowed from https://github.com/skyline69/balatro-mod-manager
 #[cfg(target_os = "linux")]
 fn configure_display_backend() -> Option<String> {
@@ -52,12 +55,31 @@ fn configure_display_backend() -> Option<String> {
 }
 
 fn main() {
+    // Release builds on Windows have no console for clap to print usage errors to,
+    // so bad arguments are logged and the app starts with the defaults instead
+    let (args, args_error) = match Args::try_parse() {
+        Ok(args) => (args, None),
+        // --help and --version
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => (Args::default(), Some(e)),
+    };
+    opencode_lib::init_tracing(&args);
+
+    if let Some(e) = args_error {
+        tracing::error!(
+            "Ignoring invalid command-line arguments: {}",
+            e.to_string().trim_end()
+        );
+    }
+
     #[cfg(target_os = "linux")]
-    {
-        if let Some(backend_note) = configure_display_backend() {
-            eprintln!("{backend_note:?}");
-        }
+    let display_backend = configure_display_backend();
+    #[cfg(not(target_os = "linux"))]
+    let display_backend = None;
+
+    if let Some(backend_note) = &display_backend {
+        tracing::info!("{backend_note}");
     }
 
-    opencode_lib::run()
+    opencode_lib::run(args, display_backend)
 }
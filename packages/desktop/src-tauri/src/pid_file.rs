@@ -0,0 +1,146 @@
+//! PID records for spawned sidecars.
+//!
+//! Every sidecar the desktop app spawns gets a small JSON record under
+//! `AppLocalData/sidecars/<pid>.json` naming its port, the binary it runs and the
+//! desktop process that owns it. On startup, records left behind by a previous
+//! run whose owner is gone are reaped: the sidecar is stopped, but only after
+//! confirming the pid still runs our sidecar binary, so servers started from a
+//! terminal (or an unrelated process that reused the pid) are never touched.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use tauri::{AppHandle, Manager, path::BaseDirectory};
+
+const RECORDS_DIR: &str = "sidecars";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SidecarRecord {
+    pid: u32,
+    port: u32,
+    /// Path of the sidecar binary the process was started from.
+    binary: PathBuf,
+    /// Pid of the desktop app that spawned the sidecar.
+    owner_pid: u32,
+}
+
+fn records_dir(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .resolve(RECORDS_DIR, BaseDirectory::AppLocalData)
+        .ok()
+}
+
+fn record_path(dir: &Path, pid: u32) -> PathBuf {
+    dir.join(format!("{pid}.json"))
+}
+
+fn is_running(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .output()
+        .map(|out| out.status.success())
+        .unwrap_or(false)
+}
+
+/// Path of the executable currently running as `pid`, if it can be determined.
+fn executable_of(pid: u32) -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    return std::fs::read_link(format!("/proc/{pid}/exe")).ok();
+
+    #[cfg(not(target_os = "linux"))]
+    return Command::new("ps")
+        .args(["-o", "comm=", "-p", &pid.to_string()])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| PathBuf::from(String::from_utf8_lossy(&out.stdout).trim()));
+}
+
+fn runs_binary(pid: u32, binary: &Path) -> bool {
+    let Some(exe) = executable_of(pid) else {
+        return false;
+    };
+
+    let binary = binary
+        .canonicalize()
+        .unwrap_or_else(|_| binary.to_path_buf());
+    exe.canonicalize().unwrap_or(exe) == binary
+}
+
+/// Records a freshly spawned sidecar.
+pub fn write(app: &AppHandle, pid: u32, port: u32) {
+    let Some(dir) = records_dir(app) else {
+        return;
+    };
+
+    let record = SidecarRecord {
+        pid,
+        port,
+        binary: crate::cli::get_sidecar_path(app),
+        owner_pid: std::process::id(),
+    };
+
+    let res = std::fs::create_dir_all(&dir).and_then(|_| {
+        std::fs::write(
+            record_path(&dir, pid),
+            serde_json::to_vec(&record).map_err(std::io::Error::other)?,
+        )
+    });
+
+    if let Err(e) = res {
+        tracing::warn!("Failed to write pid record for sidecar {pid}: {e}");
+    }
+}
+
+/// Forgets a sidecar that has exited or been killed.
+pub fn remove(app: &AppHandle, pid: u32) {
+    if let Some(dir) = records_dir(app) {
+        let _ = std::fs::remove_file(record_path(&dir, pid));
+    }
+}
+
+/// Stops sidecars orphaned by a previous run of the app and clears their records.
+pub fn reap_orphans(app: &AppHandle) {
+    let Some(dir) = records_dir(app) else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+
+        let Some(record) = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<SidecarRecord>(&bytes).ok())
+        else {
+            let _ = std::fs::remove_file(&path);
+            continue;
+        };
+
+        let owner_alive = record.owner_pid != std::process::id()
+            && is_running(record.owner_pid)
+            && std::env::current_exe().is_ok_and(|exe| runs_binary(record.owner_pid, &exe));
+        if owner_alive {
+            // Still owned by another live instance of the desktop app
+            continue;
+        }
+
+        if is_running(record.pid) && runs_binary(record.pid, &record.binary) {
+            tracing::info!(
+                "Killing orphaned sidecar {} (port {})",
+                record.pid,
+                record.port
+            );
+            let _ = Command::new("kill")
+                .args(["-KILL", &record.pid.to_string()])
+                .output();
+        }
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,222 @@
+//! The project directory the local sidecar runs in.
+//!
+//! The server treats its working directory as the current project, so the
+//! desktop app starts the sidecar in an explicitly chosen directory instead of
+//! wherever it happened to be launched from. The directory comes from the command
+//! line or the folder picker, and the last choice is remembered in the settings
+//! store. `open_project_window`, and paths forwarded by a second instance, open a
+//! project in a window of its own instead.
+
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_store::StoreExt;
+
+use crate::lifecycle::{self, ServerPhase};
+use crate::servers::{self, MAIN_WINDOW, Servers};
+use crate::{ServerReadyData, ServerState, settings_store};
+
+const PROJECT_DIR_KEY: &str = "projectDir";
+
+/// The existing directory `path` refers to, resolved against `cwd` if relative.
+pub fn resolve_dir(path: &Path, cwd: &Path) -> Result<PathBuf, String> {
+    let path = cwd.join(path);
+    let dir = path
+        .canonicalize()
+        .map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    Ok(dir)
+}
+
+/// The directory chosen in a previous session, if it still exists.
+pub fn stored(app: &AppHandle) -> Option<PathBuf> {
+    let store = app.store(settings_store(app)).ok()?;
+    let dir = PathBuf::from(store.get(PROJECT_DIR_KEY)?.as_str()?);
+    dir.is_dir().then_some(dir)
+}
+
+fn remember(app: &AppHandle, dir: &Path) {
+    let Ok(store) = app.store(settings_store(app)) else {
+        return;
+    };
+
+    store.set(PROJECT_DIR_KEY, dir.to_string_lossy().into_owned());
+    if let Err(e) = store.save() {
+        tracing::warn!("Failed to save project directory: {e}");
+    }
+}
+
+/// Makes `dir` the project of the local sidecar behind `state`, restarting it there
+/// if it runs somewhere else.
+pub async fn open(
+    app: &AppHandle,
+    state: &ServerState,
+    dir: PathBuf,
+) -> Result<ServerReadyData, String> {
+    remember(app, &dir);
+    *state.project_dir.lock().unwrap() = Some(dir.clone());
+
+    // Let a server that is still starting come up before deciding whether to move it
+    if let Ok(data) = state.wait_ready().await {
+        let local = state.child.lock().unwrap().is_some();
+        let running_in = state
+            .project_env
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|env| env.dir.clone());
+
+        // A remote server is not ours to move; the directory applies to the next local one
+        if !local || running_in.as_deref() == Some(dir.as_path()) {
+            return Ok(data);
+        }
+    }
+
+    tracing::info!("Moving server to project {}", dir.display());
+    let data = crate::restart(app, state, None).await?;
+    servers::emit(app, state, "server://changed", &data);
+
+    Ok(data)
+}
+
+/// Route of the web app showing the project `dir`.
+fn project_url(dir: &Path) -> WebviewUrl {
+    let encoded = URL_SAFE_NO_PAD.encode(dir.to_string_lossy().as_bytes());
+    WebviewUrl::App(format!("/{encoded}").into())
+}
+
+/// The project window opened for `dir`, if it is still open.
+pub fn find_window(app: &AppHandle, dir: &Path) -> Option<WebviewWindow> {
+    let label = app.state::<Servers>().window_for_project(dir)?;
+    app.get_webview_window(&label)
+}
+
+/// Opens `dir` in a new window.
+///
+/// The window gets its own sidecar started in `dir`, unless `shared` asks it to
+/// use the main window's server. Returns the window label.
+pub fn open_window(app: &AppHandle, dir: PathBuf, shared: bool) -> Result<String, String> {
+    let servers = app.state::<Servers>();
+
+    let state = if shared {
+        servers::state_for(app, MAIN_WINDOW)?
+    } else {
+        let state = ServerState::new();
+        *state.project_dir.lock().unwrap() = Some(dir.clone());
+        state
+    };
+
+    let label = format!("project-{}", uuid::Uuid::new_v4().simple());
+    servers.insert(&label, state.clone(), Some(dir.clone()));
+
+    if let Err(e) = crate::build_window(app, &label, project_url(&dir)) {
+        servers.remove(&label);
+        return Err(format!("Failed to open window: {e}"));
+    }
+    tracing::info!("Opened {} in window {label}", dir.display());
+
+    if !shared {
+        {
+            let app = app.clone();
+            let state = state.clone();
+            tauri::async_runtime::spawn(async move {
+                let res = crate::setup_server_connection(&app, &state, None).await;
+                if let Err(e) = &res {
+                    lifecycle::transition(&app, &state, ServerPhase::Stopped, |l| {
+                        l.message = Some(e.clone());
+                    });
+                }
+                state.set_status(Some(res));
+            });
+        }
+
+        tauri::async_runtime::spawn(crate::health::monitor(app.clone(), state));
+    }
+
+    Ok(label)
+}
+
+#[tauri::command]
+pub fn get_project_dir(window: WebviewWindow) -> Result<Option<PathBuf>, String> {
+    let state = servers::state_of(&window)?;
+    Ok(state.project_dir.lock().unwrap().clone())
+}
+
+/// Opens `path` as the window's project.
+#[tauri::command]
+pub async fn set_project_dir(
+    window: WebviewWindow,
+    path: String,
+) -> Result<ServerReadyData, String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let dir = resolve_dir(Path::new(&path), &cwd)?;
+    let state = servers::state_of(&window)?;
+
+    open(window.app_handle(), &state, dir).await
+}
+
+/// Lets the user pick the window's project folder; resolves to `None` if they cancel.
+#[tauri::command]
+pub async fn pick_project_dir(window: WebviewWindow) -> Result<Option<ServerReadyData>, String> {
+    let app = window.app_handle();
+    let state = servers::state_of(&window)?;
+    let current = state.project_dir.lock().unwrap().clone();
+
+    let mut dialog = app.dialog().file().set_title("Open Project");
+    if let Some(dir) = current {
+        dialog = dialog.set_directory(dir);
+    }
+
+    let Some(picked) = dialog.blocking_pick_folder() else {
+        return Ok(None);
+    };
+    let dir = picked.into_path().map_err(|e| e.to_string())?;
+
+    open(app, &state, dir).await.map(Some)
+}
+
+/// Opens `path` in a new window, with its own server unless `shared` is set, or
+/// focuses the window already showing it.
+///
+/// Resolves to the label of the window.
+#[tauri::command]
+pub async fn open_project_window(
+    app: AppHandle,
+    path: String,
+    shared: Option<bool>,
+) -> Result<String, String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let dir = resolve_dir(Path::new(&path), &cwd)?;
+
+    if let Some(window) = find_window(&app, &dir) {
+        crate::instance::focus(&window);
+        return Ok(window.label().to_string());
+    }
+    open_window(&app, dir, shared.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_resolve_dir_relative_to_cwd() {
+        let dir = TempDir::new("project");
+        let cwd = dir.parent().unwrap();
+        let name = dir.file_name().unwrap().to_str().unwrap().to_string();
+        let file = dir.join("file.txt");
+        std::fs::write(&file, "").unwrap();
+
+        assert_eq!(resolve_dir(Path::new(&name), cwd), Ok(dir.to_path_buf()));
+        assert_eq!(resolve_dir(&dir, Path::new("/")), Ok(dir.to_path_buf()));
+        assert!(resolve_dir(Path::new("does-not-exist"), cwd).is_err());
+        assert!(resolve_dir(&file, cwd).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! Which server each window talks to.
+//!
+//! Every webview window is backed by a `ServerState`: the main window by the one
+//! created at startup, project windows by their own sidecar or by sharing the main
+//! window's. Commands resolve the state of the window that invoked them, server
+//! events only reach the windows a server backs, and a sidecar is stopped once the
+//! last of its windows is gone.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, EventTarget, Manager, WebviewWindow};
+
+use crate::ServerState;
+
+pub const MAIN_WINDOW: &str = "main";
+
+struct WindowServer {
+    state: ServerState,
+    /// Project the window was opened for, if any.
+    project: Option<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Servers {
+    windows: Mutex<HashMap<String, WindowServer>>,
+    /// Shutdowns of servers whose last window closed, awaited before the app exits.
+    stopping: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Servers {
+    pub fn get(&self, label: &str) -> Option<ServerState> {
+        self.windows
+            .lock()
+            .unwrap()
+            .get(label)
+            .map(|w| w.state.clone())
+    }
+
+    pub fn insert(&self, label: &str, state: ServerState, project: Option<PathBuf>) {
+        self.windows
+            .lock()
+            .unwrap()
+            .insert(label.to_string(), WindowServer { state, project });
+    }
+
+    /// Forgets the window `label`, returning its server if no other window uses it.
+    pub fn remove(&self, label: &str) -> Option<ServerState> {
+        let mut windows = self.windows.lock().unwrap();
+        let state = windows.remove(label)?.state;
+        let shared = windows.values().any(|w| w.state.same(&state));
+
+        (!shared).then_some(state)
+    }
+
+    /// Labels of the windows backed by `state`.
+    pub fn windows_of(&self, state: &ServerState) -> Vec<String> {
+        self.windows
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, w)| w.state.same(state))
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    /// The window opened for the project `dir`.
+    pub fn window_for_project(&self, dir: &Path) -> Option<String> {
+        self.windows
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, w)| w.project.as_deref() == Some(dir))
+            .map(|(label, _)| label.clone())
+    }
+
+    /// Every distinct server, each once no matter how many windows share it.
+    pub fn all(&self) -> Vec<ServerState> {
+        let windows = self.windows.lock().unwrap();
+        let mut states: Vec<ServerState> = Vec::new();
+
+        for w in windows.values() {
+            if !states.iter().any(|s| s.same(&w.state)) {
+                states.push(w.state.clone());
+            }
+        }
+        states
+    }
+}
+
+/// The server backing the window `label`.
+pub fn state_for(app: &AppHandle, label: &str) -> Result<ServerState, String> {
+    app.state::<Servers>()
+        .get(label)
+        .ok_or_else(|| format!("No server for window {label}"))
+}
+
+/// The server backing the window a command was invoked from.
+pub fn state_of(window: &WebviewWindow) -> Result<ServerState, String> {
+    state_for(window.app_handle(), window.label())
+}
+
+/// Emits `event` to the windows backed by `state`.
+pub fn emit<S: serde::Serialize + Clone>(
+    app: &AppHandle,
+    state: &ServerState,
+    event: &str,
+    payload: S,
+) {
+    let labels = app
+        .try_state::<Servers>()
+        .map(|servers| servers.windows_of(state))
+        .unwrap_or_default();
+
+    let res = app.emit_filter(event, payload, |target| match target {
+        EventTarget::Window { label }
+        | EventTarget::Webview { label }
+        | EventTarget::WebviewWindow { label } => labels.contains(label),
+        _ => false,
+    });
+
+    if let Err(e) = res {
+        tracing::warn!("Failed to emit {event}: {e}");
+    }
+}
+
+/// Stops the server of a closed window unless another window still uses it.
+pub fn window_destroyed(app: &AppHandle, label: &str) {
+    let Some(state) = app.state::<Servers>().remove(label) else {
+        return;
+    };
+
+    tracing::info!("Last window of a server closed ({label}), stopping it");
+    let handle = {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            crate::shutdown::stop_sidecar(&app, &state).await;
+        })
+    };
+    app.state::<Servers>().stopping.lock().unwrap().push(handle);
+}
+
+/// Stops every server, including those still shutting down after their window closed.
+pub async fn stop_all(app: &AppHandle) {
+    let Some(servers) = app.try_state::<Servers>() else {
+        tracing::debug!("Server not running");
+        return;
+    };
+
+    let states = servers.all();
+    let stopping = std::mem::take(&mut *servers.stopping.lock().unwrap());
+
+    let stops = states
+        .iter()
+        .map(|state| crate::shutdown::stop_sidecar(app, state));
+    futures::future::join_all(stops).await;
+
+    for handle in stopping {
+        let _ = handle.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_stops_with_its_last_window() {
+        let servers = Servers::default();
+        let main = ServerState::new();
+        let own = ServerState::new();
+        let dir = PathBuf::from("/src/app");
+
+        servers.insert(MAIN_WINDOW, main.clone(), None);
+        servers.insert("project-a", main.clone(), None);
+        servers.insert("project-b", own.clone(), Some(dir.clone()));
+
+        assert_eq!(servers.all().len(), 2);
+        assert_eq!(
+            servers.window_for_project(&dir).as_deref(),
+            Some("project-b")
+        );
+
+        let mut shared = servers.windows_of(&main);
+        shared.sort();
+        assert_eq!(shared, [MAIN_WINDOW, "project-a"]);
+
+        assert!(servers.remove(MAIN_WINDOW).is_none());
+        assert!(servers.remove("project-a").unwrap().same(&main));
+        assert!(servers.remove("project-b").unwrap().same(&own));
+        assert!(servers.remove("project-b").is_none());
+        assert!(servers.all().is_empty());
+    }
+}
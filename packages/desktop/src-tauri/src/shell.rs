@@ -0,0 +1,349 @@
+//! The user's login shell and how to talk to it.
+//!
+//! Shells disagree on flags, quoting and on which words are builtins, so each
+//! supported family gets its own invocation strategy. The shell comes from
+//! `$SHELL` or, for GUI launches without one, from the user's passwd entry. Shells
+//! we do not know how to drive are replaced by `/bin/sh`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FALLBACK_SHELL: &str = "/bin/sh";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShellKind {
+    /// sh, dash, ksh and other shells following POSIX syntax.
+    Posix,
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+    Xonsh,
+    Elvish,
+    /// csh and tcsh.
+    Csh,
+}
+
+impl ShellKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        match name {
+            "sh" | "ash" | "dash" | "ksh" | "ksh93" | "mksh" | "oksh" | "pdksh" | "yash" => {
+                Some(Self::Posix)
+            }
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            "nu" | "nushell" => Some(Self::Nushell),
+            "xonsh" => Some(Self::Xonsh),
+            "elvish" => Some(Self::Elvish),
+            "csh" | "tcsh" => Some(Self::Csh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shell {
+    pub path: PathBuf,
+    pub kind: ShellKind,
+}
+
+impl Shell {
+    /// A shell at `path`, if it is one we know how to drive.
+    pub fn new(path: impl Into<PathBuf>) -> Option<Self> {
+        let path = path.into();
+        let kind = ShellKind::from_path(&path)?;
+        Some(Self { path, kind })
+    }
+
+    fn fallback() -> Self {
+        Self {
+            path: PathBuf::from(FALLBACK_SHELL),
+            kind: ShellKind::Posix,
+        }
+    }
+
+    /// The user's shell from `$SHELL`, then the passwd entry, then `/bin/sh`.
+    pub fn detect() -> Self {
+        let path = std::env::var_os("SHELL")
+            .filter(|shell| !shell.is_empty())
+            .map(PathBuf::from)
+            .or_else(passwd_shell);
+
+        let Some(path) = path else {
+            return Self::fallback();
+        };
+
+        match Self::new(&path) {
+            Some(shell) if path.exists() => shell,
+            _ => {
+                tracing::warn!(
+                    "Unsupported shell {}, using {FALLBACK_SHELL}",
+                    path.display()
+                );
+                Self::fallback()
+            }
+        }
+    }
+
+    /// Flags that make the shell load the user's configuration and run the
+    /// command string that follows.
+    pub fn command_flags(&self) -> &'static [&'static str] {
+        match self.kind {
+            ShellKind::Posix
+            | ShellKind::Bash
+            | ShellKind::Zsh
+            | ShellKind::Fish
+            | ShellKind::Nushell
+            | ShellKind::Xonsh => &["-i", "-l", "-c"],
+            // Elvish has no login mode, csh only accepts `-l` on its own
+            ShellKind::Elvish | ShellKind::Csh => &["-c"],
+        }
+    }
+
+    /// Quotes `word` so the shell passes it on verbatim as a single argument.
+    pub fn quote(&self, word: &str) -> String {
+        match self.kind {
+            ShellKind::Posix | ShellKind::Bash | ShellKind::Zsh | ShellKind::Csh => {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+            ShellKind::Fish => format!("'{}'", word.replace('\\', r"\\").replace('\'', r"\'")),
+            ShellKind::Elvish => format!("'{}'", word.replace('\'', "''")),
+            ShellKind::Xonsh => {
+                let mut quoted = String::from("'");
+                for c in word.chars() {
+                    match c {
+                        '\\' => quoted.push_str(r"\\"),
+                        '\'' => quoted.push_str(r"\'"),
+                        '\n' => quoted.push_str(r"\n"),
+                        '\r' => quoted.push_str(r"\r"),
+                        c => quoted.push(c),
+                    }
+                }
+                quoted.push('\'');
+                quoted
+            }
+            // Raw strings have no escapes; add `#`s until the closing delimiter is unique
+            ShellKind::Nushell => {
+                let mut hashes = String::from("#");
+                while word.contains(&format!("'{hashes}")) {
+                    hashes.push('#');
+                }
+                format!("r{hashes}'{word}'{hashes}")
+            }
+        }
+    }
+
+    /// A command line running the external `program` with `args`, bypassing any
+    /// builtin of the same name.
+    pub fn external(&self, program: &str, args: &[&str]) -> String {
+        let mut line = match self.kind {
+            ShellKind::Nushell => format!("^{program}"),
+            ShellKind::Elvish => format!("e:{program}"),
+            _ => program.to_string(),
+        };
+
+        for arg in args {
+            line.push(' ');
+            line.push_str(&self.quote(arg));
+        }
+        line
+    }
+
+    /// A process running `script` in this shell.
+    pub fn command(&self, script: &str) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(self.command_flags()).arg(script);
+        command
+    }
+}
+
+/// Login shell field of a `/etc/passwd` line, if it belongs to `uid`.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn parse_passwd_line(line: &str, uid: &str) -> Option<PathBuf> {
+    let fields: Vec<_> = line.trim_end().split(':').collect();
+    let [_, _, entry_uid, _, _, _, shell] = fields[..] else {
+        return None;
+    };
+
+    (entry_uid == uid && !shell.is_empty()).then(|| PathBuf::from(shell))
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|out| !out.is_empty())
+}
+
+/// Login shell from the user database.
+fn passwd_shell() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    return command_output("id", &["-un"])
+        .and_then(|user| {
+            command_output(
+                "dscl",
+                &[".", "-read", &format!("/Users/{user}"), "UserShell"],
+            )
+        })
+        .and_then(|out| Some(PathBuf::from(out.strip_prefix("UserShell:")?.trim())));
+
+    #[cfg(not(target_os = "macos"))]
+    return {
+        let uid = command_output("id", &["-u"])?;
+        command_output("getent", &["passwd", &uid])
+            .and_then(|line| parse_passwd_line(&line, &uid))
+            .or_else(|| {
+                std::fs::read_to_string("/etc/passwd")
+                    .ok()?
+                    .lines()
+                    .find_map(|line| parse_passwd_line(line, &uid))
+            })
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRICKY: &str = "it's a \"path\" with \\ and\nnewline";
+
+    const POSIX_CASES: &[&str] = &[
+        "/Users/me/My Projects/app",
+        "  leading and trailing  ",
+        "",
+        "it's",
+        "\"double\" quoted",
+        "'",
+        "$HOME `id` $(id) ; rm -rf / && echo | cat > out",
+        "back\\slash\nnewline",
+        "/home/ユーザー/プロジェクト",
+        "café 🚀",
+        "naïve'ünïcode",
+    ];
+
+    fn shell(path: &str) -> Shell {
+        Shell::new(path).unwrap()
+    }
+
+    /// Runs `args` through a real POSIX-family shell and returns the words it saw.
+    fn round_trip(shell: &Shell, args: &[&str]) -> Vec<String> {
+        let mut words = vec!["%s\\0"];
+        words.extend(args);
+        let script = shell.external("printf", &words);
+
+        let output = Command::new(&shell.path)
+            .arg("-c")
+            .arg(&script)
+            .output()
+            .expect("Failed to run shell");
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_kind_from_path() {
+        assert_eq!(shell("/bin/dash").kind, ShellKind::Posix);
+        assert_eq!(shell("/opt/homebrew/bin/bash").kind, ShellKind::Bash);
+        assert_eq!(shell("/bin/zsh").kind, ShellKind::Zsh);
+        assert_eq!(shell("/usr/local/bin/fish").kind, ShellKind::Fish);
+        assert_eq!(shell("/home/me/.cargo/bin/nu").kind, ShellKind::Nushell);
+        assert_eq!(shell("/usr/bin/xonsh").kind, ShellKind::Xonsh);
+        assert_eq!(shell("/usr/bin/elvish").kind, ShellKind::Elvish);
+        assert_eq!(shell("/bin/tcsh").kind, ShellKind::Csh);
+        assert!(Shell::new("/usr/bin/pwsh").is_none());
+        assert!(Shell::new("").is_none());
+    }
+
+    #[test]
+    fn test_posix() {
+        let sh = shell("/bin/sh");
+        assert_eq!(sh.command_flags(), ["-i", "-l", "-c"]);
+        assert_eq!(sh.quote("it's"), r"'it'\''s'");
+        assert_eq!(sh.external("env", &["-0"]), "env '-0'");
+        assert_eq!(round_trip(&sh, POSIX_CASES), POSIX_CASES);
+    }
+
+    #[test]
+    fn test_bash() {
+        let bash = shell("/bin/bash");
+        assert_eq!(bash.command_flags(), ["-i", "-l", "-c"]);
+
+        if bash.path.exists() {
+            assert_eq!(round_trip(&bash, POSIX_CASES), POSIX_CASES);
+            assert_eq!(
+                round_trip(&bash, &[TRICKY, "!history"]),
+                [TRICKY, "!history"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_zsh() {
+        let zsh = shell("/bin/zsh");
+        assert_eq!(zsh.command_flags(), ["-i", "-l", "-c"]);
+        assert_eq!(zsh.quote("a'b"), r"'a'\''b'");
+    }
+
+    #[test]
+    fn test_fish() {
+        let fish = shell("/usr/bin/fish");
+        assert_eq!(fish.command_flags(), ["-i", "-l", "-c"]);
+        assert_eq!(fish.quote(r"it's C:\dir"), r"'it\'s C:\\dir'");
+        assert_eq!(fish.external("env", &["-0"]), "env '-0'");
+    }
+
+    #[test]
+    fn test_nushell() {
+        let nu = shell("/usr/bin/nu");
+        assert_eq!(nu.command_flags(), ["-i", "-l", "-c"]);
+        assert_eq!(nu.quote(r#"it's "x""#), r#"r#'it's "x"'#"#);
+        assert_eq!(nu.quote("'#"), "r##''#'##");
+        assert_eq!(nu.external("env", &["-0"]), "^env r#'-0'#");
+    }
+
+    #[test]
+    fn test_xonsh() {
+        let xonsh = shell("/usr/bin/xonsh");
+        assert_eq!(xonsh.command_flags(), ["-i", "-l", "-c"]);
+        assert_eq!(
+            xonsh.quote(TRICKY),
+            r#"'it\'s a "path" with \\ and\nnewline'"#
+        );
+    }
+
+    #[test]
+    fn test_elvish() {
+        let elvish = shell("/usr/bin/elvish");
+        assert_eq!(elvish.command_flags(), ["-c"]);
+        assert_eq!(elvish.quote("it's"), "'it''s'");
+        assert_eq!(elvish.external("env", &["-0"]), "e:env '-0'");
+    }
+
+    #[test]
+    fn test_csh() {
+        let tcsh = shell("/bin/tcsh");
+        assert_eq!(tcsh.command_flags(), ["-c"]);
+        assert_eq!(tcsh.quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_parse_passwd_line() {
+        let line = "me:x:1000:1000:Me,,,:/home/me:/usr/bin/fish\n";
+        assert_eq!(
+            parse_passwd_line(line, "1000"),
+            Some(PathBuf::from("/usr/bin/fish"))
+        );
+        assert_eq!(parse_passwd_line(line, "0"), None);
+        assert_eq!(parse_passwd_line("me:x:1000:1000::/home/me:", "1000"), None);
+        assert_eq!(parse_passwd_line("garbage", "1000"), None);
+    }
+}
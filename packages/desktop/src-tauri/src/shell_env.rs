@@ -0,0 +1,222 @@
+//! Login-shell environment capture.
+//!
+//! Apps launched from the Dock or a desktop menu do not see the `PATH` and other
+//! variables users set up in their shell rc files, which the sidecar needs to find
+//! tools like `git`. Instead of running every sidecar invocation through an
+//! interactive login shell, the shell runs once per session to dump its
+//! environment, and the sidecar is then executed directly with that environment.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::cli::stdout_with_timeout;
+use crate::shell::Shell;
+
+/// How long the login shell gets to print its environment.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Variables describing the capturing shell itself rather than the user's setup.
+const SHELL_LOCAL_VARS: &[&str] = &["_", "SHLVL", "PWD", "OLDPWD"];
+
+static LOGIN_ENV: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// The login-shell environment, captured on first use and cached for the session.
+///
+/// Falls back to the app's own environment if the shell fails, hangs or prints
+/// something unparsable.
+pub fn login_env() -> &'static HashMap<String, String> {
+    LOGIN_ENV.get_or_init(|| {
+        let shell = Shell::detect();
+        let started = Instant::now();
+
+        match capture(&shell, CAPTURE_TIMEOUT) {
+            Ok(env) => {
+                tracing::info!(
+                    "Captured {} variables from {} in {:?}",
+                    env.len(),
+                    shell.path.display(),
+                    started.elapsed()
+                );
+                env
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to capture environment from {}, using process env: {e}",
+                    shell.path.display()
+                );
+                std::env::vars_os()
+                    .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+                    .collect()
+            }
+        }
+    })
+}
+
+/// Runs `shell` as an interactive login shell and reads back its environment.
+fn capture(shell: &Shell, timeout: Duration) -> Result<HashMap<String, String>, String> {
+    use std::os::unix::process::CommandExt;
+
+    // rc files may print banners or prompts; the delimiters frame the actual dump.
+    // The delimiter is printed in two halves so that `$_`, which some shells set to
+    // the previous command's last argument, never contains it whole.
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let delimiter = format!("__OPENCODE_ENV_{id}");
+    let print_delimiter = shell.external("printf", &["%s%s", "__OPENCODE_ENV_", &id]);
+    let script = [
+        print_delimiter.clone(),
+        shell.external("env", &["-0"]),
+        print_delimiter,
+    ]
+    .join("; ");
+
+    let mut command = shell.command(&script);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    // Keep the shell away from the terminal the app may have been started from. A
+    // new process group alone is not enough: it would be a background group of that
+    // terminal, and an interactive shell touching it is stopped by SIGTTIN.
+    // Safety: `setsid` is async-signal-safe.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command
+        .spawn()
+        .map_err(|e| format!("failed to spawn: {e}"))?;
+
+    let output = stdout_with_timeout(child, timeout)?;
+    parse_env_dump(&output, delimiter.as_bytes()).ok_or_else(|| "no environment found".into())
+}
+
+/// Extracts the `env -0` output framed by `delimiter` from `output`.
+fn parse_env_dump(output: &[u8], delimiter: &[u8]) -> Option<HashMap<String, String>> {
+    let find = |haystack: &[u8]| {
+        haystack
+            .windows(delimiter.len())
+            .position(|window| window == delimiter)
+    };
+
+    let start = find(output)? + delimiter.len();
+    let end = start + find(&output[start..])?;
+
+    let env: HashMap<_, _> = output[start..end]
+        .split(|b| *b == 0)
+        .filter_map(|entry| {
+            let entry = std::str::from_utf8(entry).ok()?;
+            let (key, value) = entry.split_once('=')?;
+            (!key.is_empty() && !SHELL_LOCAL_VARS.contains(&key))
+                .then(|| (key.to_string(), value.to_string()))
+        })
+        .collect();
+
+    (!env.is_empty()).then_some(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::os::unix::process::CommandExt;
+    use std::ptr::null_mut;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_env_dump_ignores_rc_output() {
+        let output =
+            b"Welcome back!\n__D__PATH=/usr/bin:/opt/bin\0MULTI=a\nb\0EQ=x=y\0SHLVL=2\0__D__\n";
+        let env = parse_env_dump(output, b"__D__").unwrap();
+
+        assert_eq!(env.len(), 3);
+        assert_eq!(env["PATH"], "/usr/bin:/opt/bin");
+        assert_eq!(env["MULTI"], "a\nb");
+        assert_eq!(env["EQ"], "x=y");
+    }
+
+    #[test]
+    fn test_parse_env_dump_requires_both_delimiters() {
+        assert!(parse_env_dump(b"__D__PATH=/usr/bin\0", b"__D__").is_none());
+        assert!(parse_env_dump(b"PATH=/usr/bin\0", b"__D__").is_none());
+    }
+
+    #[test]
+    fn test_capture_from_sh() {
+        let env = capture(&Shell::new("/bin/sh").unwrap(), CAPTURE_TIMEOUT).unwrap();
+        assert!(env.contains_key("PATH"));
+    }
+
+    /// Set for the copy of the test binary `test_capture_under_terminal` starts.
+    const IN_TERMINAL_ENV: &str = "OPENCODE_TEST_IN_TERMINAL";
+
+    /// Run by `test_capture_under_terminal` in a session that owns a pty.
+    #[test]
+    #[ignore]
+    fn capture_in_terminal() {
+        if std::env::var_os(IN_TERMINAL_ENV).is_none() {
+            return;
+        }
+        for shell in ["/bin/sh", "/bin/bash"] {
+            if let Some(shell) = Shell::new(shell).filter(|shell| shell.path.exists()) {
+                capture(&shell, CAPTURE_TIMEOUT).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_capture_under_terminal() {
+        let (mut master, mut slave) = (-1, -1);
+        // Safety: the out pointers are valid and the optional arguments are null
+        let res =
+            unsafe { libc::openpty(&mut master, &mut slave, null_mut(), null_mut(), null_mut()) };
+        assert_eq!(res, 0, "openpty: {}", std::io::Error::last_os_error());
+        // Safety: openpty returned both descriptors to us
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+        // Like an app started from a shell: the pty is the controlling terminal of
+        // the session and the process is in its foreground group
+        let mut command = std::process::Command::new(std::env::current_exe().unwrap());
+        command
+            .args([
+                "--exact",
+                "shell_env::tests::capture_in_terminal",
+                "--ignored",
+            ])
+            .env(IN_TERMINAL_ENV, "1")
+            .stdin(slave.try_clone().unwrap())
+            .stdout(slave.try_clone().unwrap())
+            .stderr(slave);
+        // Safety: only async-signal-safe calls
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let started = Instant::now();
+        let mut child = command.spawn().unwrap();
+        // Drop our copies of the slave so reading the output ends with the child
+        drop(command);
+        let mut output = Vec::new();
+        let _ = std::fs::File::from(master).read_to_end(&mut output);
+        let status = child.wait().unwrap();
+
+        assert!(
+            status.success(),
+            "capture failed under a terminal:\n{}",
+            String::from_utf8_lossy(&output)
+        );
+        assert!(started.elapsed() < CAPTURE_TIMEOUT);
+    }
+}
@@ -0,0 +1,96 @@
+//! Staged sidecar shutdown.
+//!
+//! Instead of killing the server outright, the desktop app first asks it to
+//! dispose its instances over HTTP (flushing session state and closing provider
+//! streams), then sends SIGTERM on Unix and waits for it to exit. Windows has no
+//! SIGTERM, so there the server gets the rest of the grace period to exit after
+//! disposing. Only when the grace period runs out is the process force-killed.
+
+use std::time::{Duration, Instant};
+
+use tauri::AppHandle;
+
+use crate::lifecycle::{self, ServerPhase};
+use crate::supervisor::wait_exit;
+use crate::{ServerState, get_duration_setting};
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const SHUTDOWN_TIMEOUT_KEY: &str = "sidecarShutdownTimeoutMs";
+const SHUTDOWN_TIMEOUT_ENV: &str = "OPENCODE_SHUTDOWN_TIMEOUT_MS";
+
+fn shutdown_timeout(app: &AppHandle) -> Duration {
+    get_duration_setting(app, SHUTDOWN_TIMEOUT_KEY, SHUTDOWN_TIMEOUT_ENV)
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT)
+}
+
+/// Asks the server to release its resources before it goes away.
+async fn request_dispose(url: &str, password: Option<&str>, timeout: Duration) {
+    let Ok(client) = reqwest::Client::builder().timeout(timeout).build() else {
+        return;
+    };
+
+    let mut req = client.post(format!("{}/global/dispose", url.trim_end_matches('/')));
+    if let Some(password) = password {
+        req = req.basic_auth("opencode", Some(password));
+    }
+
+    if let Err(e) = req.send().await {
+        tracing::warn!("Failed to dispose server before shutdown: {e}");
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) {
+    let _ = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .output();
+}
+
+/// Stops the local sidecar behind `state`, giving it a chance to shut down cleanly
+/// first.
+///
+/// Marks the server as intentionally stopped so the supervisor does not restart it.
+pub async fn stop_sidecar(app: &AppHandle, state: &ServerState) {
+    state.stop();
+
+    let Some(child) = state.child.lock().unwrap().take() else {
+        tracing::debug!("Server state missing");
+        return;
+    };
+    let exit = state.exit.lock().unwrap().take();
+
+    let pid = child.pid();
+    let grace = shutdown_timeout(app);
+    let started = Instant::now();
+
+    if let Some(ready) = state.ready_data() {
+        request_dispose(&ready.url, ready.password.as_deref(), grace).await;
+    }
+
+    let exited = match exit {
+        Some(mut exit) => {
+            // Windows has no SIGTERM; the dispose request above is the graceful part
+            #[cfg(unix)]
+            terminate(pid);
+            let remaining = grace.saturating_sub(started.elapsed());
+            tokio::time::timeout(remaining, wait_exit(&mut exit))
+                .await
+                .is_ok()
+        }
+        None => false,
+    };
+
+    if exited {
+        tracing::info!("Sidecar {pid} stopped gracefully");
+    } else {
+        if started.elapsed() >= grace {
+            tracing::warn!("Sidecar {pid} did not stop within {grace:?}, killing it");
+        }
+        let _ = child.kill();
+    }
+
+    crate::release_child(app, pid);
+    lifecycle::transition(app, state, ServerPhase::Stopped, |_| {});
+
+    tracing::info!("Sidecar shutdown took {:?}", started.elapsed());
+}
@@ -0,0 +1,276 @@
+//! Sidecar supervision.
+//!
+//! When a locally spawned `opencode-cli serve` exits without the desktop app asking
+//! it to, the supervisor records why it died and restarts it on the same port and
+//! password, so the `ServerReadyData` already handed to the webview stays valid.
+//! Should that port have been taken in the meantime, the new one is published
+//! through `server://changed`.
+//! Restarts back off exponentially, and a crash loop makes the supervisor give up
+//! and surface the last crash through `ServerState`.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::lifecycle::{self, ServerPhase};
+use crate::logs::LogState;
+use crate::servers;
+use crate::{ServerReadyData, ServerState};
+
+/// Number of trailing log entries kept in a crash report.
+const CRASH_LOG_LINES: usize = 20;
+
+/// How the sidecar process ended, as reported by the shell plugin.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SidecarExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for SidecarExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.error, self.code, self.signal) {
+            (Some(error), _, _) => write!(f, "error: {error}"),
+            (None, Some(code), _) => write!(f, "exit code {code}"),
+            (None, None, Some(signal)) => write!(f, "signal {signal}"),
+            (None, None, None) => write!(f, "unknown exit status"),
+        }
+    }
+}
+
+/// Resolves to `Some` once the sidecar process has exited.
+pub type ExitWatch = watch::Receiver<Option<SidecarExit>>;
+
+/// Waits until the sidecar behind `exit` has terminated.
+pub async fn wait_exit(exit: &mut ExitWatch) -> SidecarExit {
+    match exit.wait_for(Option::is_some).await {
+        Ok(status) => status.clone().unwrap(),
+        Err(_) => SidecarExit {
+            code: None,
+            signal: None,
+            error: Some("sidecar event stream closed".to_string()),
+        },
+    }
+}
+
+/// What the supervisor knows about the last unexpected sidecar exit.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CrashReport {
+    pub exit: SidecarExit,
+    pub logs: Vec<String>,
+}
+
+/// Exponential backoff with a crash-loop limit.
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Crashes tolerated within `crash_window` before the supervisor gives up.
+    pub max_crashes: usize,
+    pub crash_window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_crashes: 5,
+            crash_window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before the restart that follows the `crashes`-th crash in the window.
+    pub fn backoff(&self, crashes: usize) -> Duration {
+        let exponent = crashes.saturating_sub(1).min(16) as u32;
+        self.initial_backoff
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_backoff)
+    }
+}
+
+/// Sliding window of recent crash timestamps.
+#[derive(Default)]
+struct CrashHistory(VecDeque<Instant>);
+
+impl CrashHistory {
+    /// Records a crash at `now` and returns how many crashes fall within `window`.
+    fn record(&mut self, now: Instant, window: Duration) -> usize {
+        while self
+            .0
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > window)
+        {
+            self.0.pop_front();
+        }
+        self.0.push_back(now);
+        self.0.len()
+    }
+}
+
+fn recent_logs(app: &AppHandle) -> Vec<String> {
+    let Some(log_state) = app.try_state::<LogState>() else {
+        return Vec::new();
+    };
+
+    log_state
+        .recent(CRASH_LOG_LINES)
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Watches a local sidecar and restarts it whenever it exits unexpectedly.
+///
+/// Returns once the sidecar is stopped on purpose (which bumps the server
+/// `generation` this supervisor was started for) or the crash-loop limit is reached.
+pub async fn supervise(
+    app: AppHandle,
+    state: ServerState,
+    mut port: u32,
+    password: String,
+    mut exit: ExitWatch,
+    generation: u64,
+) {
+    let policy = RestartPolicy::default();
+    let mut history = CrashHistory::default();
+    let stopped = || state.generation() != generation;
+
+    loop {
+        let status = wait_exit(&mut exit).await;
+
+        if stopped() {
+            tracing::info!("Sidecar exited after shutdown ({status})");
+            return;
+        }
+
+        // A crashed child is still registered; an intentional kill has already taken it
+        if let Some(child) = state.child.lock().unwrap().take() {
+            crate::release_child(&app, child.pid());
+        }
+
+        let report = CrashReport {
+            exit: status,
+            logs: recent_logs(&app),
+        };
+        tracing::error!("Sidecar crashed ({})", report.exit);
+        state.set_crash(Some(report.clone()));
+        lifecycle::transition(&app, &state, ServerPhase::Crashed, |l| {
+            l.exit = Some(report.exit.clone());
+        });
+
+        let crashes = history.record(Instant::now(), policy.crash_window);
+        if crashes > policy.max_crashes {
+            tracing::error!(
+                "Sidecar crashed {crashes} times within {:?}, giving up",
+                policy.crash_window
+            );
+            let error = format!(
+                "OpenCode Server keeps crashing ({}). Logs:\n{}",
+                report.exit,
+                report.logs.join("\n")
+            );
+            lifecycle::transition(&app, &state, ServerPhase::Stopped, |l| {
+                l.exit = Some(report.exit.clone());
+                l.message = Some(format!("Crashed {crashes} times, not restarting"));
+            });
+            state.set_status(Some(Err(error)));
+            return;
+        }
+
+        let delay = policy.backoff(crashes);
+        tracing::info!(
+            "Restarting sidecar in {delay:?} (crash {crashes}/{})",
+            policy.max_crashes
+        );
+        lifecycle::transition(&app, &state, ServerPhase::Restarting, |l| {
+            l.attempt = Some(crashes);
+            l.retry_in = Some(delay);
+        });
+        tokio::time::sleep(delay).await;
+
+        if stopped() {
+            return;
+        }
+
+        exit = loop {
+            match crate::spawn_local_server(&app, &state, Some(port), &password).await {
+                Ok(server) => {
+                    if stopped() {
+                        let pid = server.child.pid();
+                        let _ = server.child.kill();
+                        crate::release_child(&app, pid);
+                        return;
+                    }
+                    tracing::info!("Sidecar restarted on port {}", server.port);
+                    crate::register_child(&app, &state, server.child, server.exit.clone());
+
+                    if server.port != port {
+                        port = server.port;
+                        let data = ServerReadyData {
+                            url: format!("http://127.0.0.1:{port}"),
+                            password: Some(password.clone()),
+                        };
+                        state.set_status(Some(Ok(data.clone())));
+                        servers::emit(&app, &state, "server://changed", &data);
+                    }
+                    break server.exit;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to restart sidecar: {e}");
+                    if stopped() {
+                        return;
+                    }
+
+                    let crashes = history.record(Instant::now(), policy.crash_window);
+                    if crashes > policy.max_crashes {
+                        lifecycle::transition(&app, &state, ServerPhase::Stopped, |l| {
+                            l.message = Some(e.clone());
+                        });
+                        state.set_status(Some(Err(e)));
+                        return;
+                    }
+
+                    let delay = policy.backoff(crashes);
+                    lifecycle::transition(&app, &state, ServerPhase::Restarting, |l| {
+                        l.attempt = Some(crashes);
+                        l.retry_in = Some(delay);
+                    });
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(1));
+        assert_eq!(policy.backoff(4), Duration::from_secs(4));
+        assert_eq!(policy.backoff(100), policy.max_backoff);
+    }
+
+    #[test]
+    fn test_crash_history_forgets_old_crashes() {
+        let mut history = CrashHistory::default();
+        let window = Duration::from_secs(60);
+        let start = Instant::now();
+
+        assert_eq!(history.record(start, window), 1);
+        assert_eq!(history.record(start + Duration::from_secs(10), window), 2);
+        assert_eq!(history.record(start + Duration::from_secs(100), window), 1);
+    }
+}
@@ -0,0 +1,35 @@
+//! Fixtures shared by unit tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A new, empty directory under the system temp dir, removed again on drop so a
+/// failing assertion does not leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates `opencode-<prefix>-<uuid>`; the path is canonical so tests can
+    /// compare it with resolved paths.
+    pub fn new(prefix: &str) -> Self {
+        let dir = std::env::temp_dir()
+            .canonicalize()
+            .unwrap()
+            .join(format!("opencode-{prefix}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
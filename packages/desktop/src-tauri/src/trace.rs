@@ -0,0 +1,254 @@
+//! `tracing` setup for the desktop core.
+//!
+//! Events go to stderr for development and, through `LogLayer`, into `LogState`
+//! as desktop entries, so they are redacted, kept for `get_server_logs` and
+//! written to `desktop.log` next to `sidecar.log`. That file is the only place
+//! they end up in Windows release builds, which have no console.
+//!
+//! The level comes from `OPENCODE_DESKTOP_LOG` (an `EnvFilter` directive such as
+//! `debug` or `opencode_lib::health=trace`), then `--log-level`, then the
+//! `logLevel` setting once the store is available, and defaults to `info`.
+
+use std::fmt::Write as _;
+use std::sync::{Mutex, OnceLock};
+
+use clap::ValueEnum;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Registry, reload};
+
+use crate::Args;
+use crate::args::LogLevel;
+use crate::logs::{LogEntry, LogState, LogStream};
+
+pub const LOG_FILTER_ENV: &str = "OPENCODE_DESKTOP_LOG";
+const LOG_LEVEL_KEY: &str = "logLevel";
+
+/// Target of the events echoing sidecar output, which is stored separately.
+pub const SIDECAR_TARGET: &str = "sidecar";
+
+/// Desktop entries recorded before `LogState` exists, kept at most.
+const MAX_EARLY_ENTRIES: usize = 200;
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+static SINK: OnceLock<LogState> = OnceLock::new();
+static EARLY: Mutex<Vec<LogEntry>> = Mutex::new(Vec::new());
+
+fn directives(level: LogLevel) -> String {
+    let level = level.as_server_arg().to_ascii_lowercase();
+    // Dependencies only report warnings unless asked for through the env var
+    format!("warn,opencode_lib={level},opencode_desktop={level},{SIDECAR_TARGET}={level}")
+}
+
+/// Installs the global subscriber. Called once, first thing in `main`.
+pub fn init_tracing(args: &Args) {
+    let filter = std::env::var(LOG_FILTER_ENV)
+        .ok()
+        .and_then(|directives| {
+            EnvFilter::try_new(&directives)
+                .inspect_err(|e| eprintln!("Ignoring invalid {LOG_FILTER_ENV}: {e}"))
+                .ok()
+        })
+        .unwrap_or_else(|| EnvFilter::new(directives(args.log_level.unwrap_or(LogLevel::Info))));
+
+    let (filter, handle) = reload::Layer::new(filter);
+    let console = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+
+    let res = tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(LogLayer)
+        .try_init();
+    if res.is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+/// Applies the `logLevel` setting unless the env var or `--log-level` chose one.
+pub fn apply_settings(app: &AppHandle, args: &Args) {
+    if std::env::var_os(LOG_FILTER_ENV).is_some() || args.log_level.is_some() || args.safe_mode {
+        return;
+    }
+
+    let Some(level) = app
+        .store(crate::settings_store(app))
+        .ok()
+        .and_then(|store| store.get(LOG_LEVEL_KEY))
+        .and_then(|v| v.as_str().and_then(|s| LogLevel::from_str(s, true).ok()))
+    else {
+        return;
+    };
+
+    if let Some(handle) = FILTER.get()
+        && let Err(e) = handle.reload(EnvFilter::new(directives(level)))
+    {
+        tracing::warn!("Failed to apply log level {level:?}: {e}");
+        return;
+    }
+    tracing::debug!("Log level {level:?} from settings");
+}
+
+/// Sends desktop entries to `log_state`, including those recorded so far.
+pub fn attach(log_state: &LogState) {
+    if SINK.set(log_state.clone()).is_err() {
+        return;
+    }
+    for entry in std::mem::take(&mut *EARLY.lock().unwrap()) {
+        log_state.push(entry);
+    }
+}
+
+fn record(entry: LogEntry) {
+    match SINK.get() {
+        Some(log_state) => {
+            log_state.push(entry);
+        }
+        None => {
+            let mut early = EARLY.lock().unwrap();
+            if early.len() < MAX_EARLY_ENTRIES {
+                early.push(entry);
+            }
+        }
+    }
+}
+
+/// Echoes a line of sidecar output to the console at the level it was printed with.
+pub fn echo_sidecar(entry: &LogEntry) {
+    let pid = entry.pid;
+    match entry.level {
+        Some(LogLevel::Error) => tracing::error!(target: SIDECAR_TARGET, pid, "{}", entry.message),
+        Some(LogLevel::Warn) => tracing::warn!(target: SIDECAR_TARGET, pid, "{}", entry.message),
+        Some(LogLevel::Debug) => tracing::debug!(target: SIDECAR_TARGET, pid, "{}", entry.message),
+        Some(LogLevel::Info) | None => {
+            tracing::info!(target: SIDECAR_TARGET, pid, "{}", entry.message)
+        }
+    }
+}
+
+fn level_of(level: &Level) -> LogLevel {
+    match *level {
+        Level::ERROR => LogLevel::Error,
+        Level::WARN => LogLevel::Warn,
+        Level::INFO => LogLevel::Info,
+        Level::DEBUG | Level::TRACE => LogLevel::Debug,
+    }
+}
+
+/// Collects the message and fields of an event; a `pid` field tags the entry.
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: String,
+    pid: Option<u32>,
+}
+
+impl Visit for EventVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "pid" {
+            self.pid = u32::try_from(value).ok();
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if field.name() == "pid" {
+            self.pid = u32::try_from(value).ok();
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// Turns events into desktop `LogEntry`s, prefixed with the spans they occurred in.
+struct LogLayer;
+
+impl<S> Layer<S> for LogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() == SIDECAR_TARGET {
+            return;
+        }
+
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+
+        let mut message = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                let _ = write!(message, "{}:", span.name());
+            }
+            message.push(' ');
+        }
+        message.push_str(&visitor.message);
+        message.push_str(&visitor.fields);
+
+        let mut entry = LogEntry::new(LogStream::Desktop, visitor.pid, message);
+        entry.level = Some(level_of(metadata.level()));
+        record(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives_parse() {
+        for level in LogLevel::value_variants() {
+            assert!(EnvFilter::try_new(directives(*level)).is_ok());
+        }
+        assert_eq!(
+            directives(LogLevel::Debug),
+            "warn,opencode_lib=debug,opencode_desktop=debug,sidecar=debug"
+        );
+    }
+
+    #[test]
+    fn test_layer_records_spans_and_fields() {
+        let subscriber = tracing_subscriber::registry().with(LogLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("startup").entered();
+            let _phase = tracing::info_span!("spawn").entered();
+            tracing::warn!(pid = 42u32, attempt = 2, "Port {} is in use", 4096);
+        });
+
+        let early = EARLY.lock().unwrap();
+        let entry = early
+            .iter()
+            .find(|e| e.message.contains("Port 4096"))
+            .unwrap();
+        assert_eq!(
+            entry.message,
+            "startup:spawn: Port 4096 is in use attempt=2"
+        );
+        assert_eq!(entry.pid, Some(42));
+        assert_eq!(entry.level, Some(LogLevel::Warn));
+        assert_eq!(entry.stream, LogStream::Desktop);
+    }
+}
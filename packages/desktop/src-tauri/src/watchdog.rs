@@ -0,0 +1,97 @@
+//! Unix parent-death watchdog for reliable child process cleanup.
+//!
+//! This is the Unix counterpart of the Windows Job Object in `job_object.rs`.
+//! For every sidecar we spawn a tiny `/bin/sh` helper whose stdin is a pipe held
+//! open by the desktop app. When the app goes away for any reason, the kernel
+//! closes our end of the pipe, the helper's `read` hits EOF, and it terminates the
+//! sidecar (SIGTERM, then SIGKILL after a grace period).
+//!
+//! This works even if:
+//! - The desktop app crashes or is SIGKILLed
+//! - The RunEvent::Exit handler never runs
+//!
+//! The helper runs in its own process group so that a signal aimed at the app's
+//! group (e.g. Ctrl-C in a terminal) cannot take it down before it has done its job.
+
+use std::collections::HashMap;
+use std::io::Result;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+
+/// Blocks until stdin reaches EOF, then stops the process given as `$1`.
+const WATCHDOG_SCRIPT: &str = r#"
+while read -r _; do :; done
+kill -TERM "$1" 2>/dev/null || exit 0
+sleep 2
+kill -KILL "$1" 2>/dev/null
+exit 0
+"#;
+
+/// A helper process that kills `pid` once the desktop app exits.
+///
+/// Dropping the watchdog disarms it: the helper is killed without touching `pid`.
+pub struct Watchdog {
+    helper: Child,
+}
+
+impl Watchdog {
+    /// Spawns a watchdog for the process `pid`.
+    pub fn spawn(pid: u32) -> Result<Self> {
+        let helper = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(WATCHDOG_SCRIPT)
+            .arg("opencode-watchdog")
+            .arg(pid.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+
+        Ok(Self { helper })
+    }
+
+    /// Process ID of the helper itself.
+    pub fn helper_pid(&self) -> u32 {
+        self.helper.id()
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        // SIGKILL the helper first so it never acts on a pid that may get reused
+        let _ = self.helper.kill();
+        let _ = self.helper.wait();
+    }
+}
+
+/// Holds the watchdogs that ensure sidecars are killed when the app exits.
+#[derive(Default)]
+pub struct WatchdogState {
+    watchdogs: Mutex<HashMap<u32, Watchdog>>,
+}
+
+impl WatchdogState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn assign_pid(&self, pid: u32) {
+        match Watchdog::spawn(pid) {
+            Ok(watchdog) => {
+                tracing::debug!(
+                    "Watching process {pid} with watchdog {} for automatic cleanup",
+                    watchdog.helper_pid()
+                );
+                self.watchdogs.lock().unwrap().insert(pid, watchdog);
+            }
+            Err(e) => tracing::error!("Failed to spawn watchdog for process {pid}: {e}"),
+        }
+    }
+
+    /// Disarms the watchdog for `pid` once that process has exited or been killed.
+    pub fn release(&self, pid: u32) {
+        self.watchdogs.lock().unwrap().remove(&pid);
+    }
+}
@@ -0,0 +1,88 @@
+//! A SIGKILLed desktop app must not leave its sidecar running.
+//!
+//! The test re-executes its own binary as a stand-in for the desktop app. That
+//! process spawns a long-running "server", arms a watchdog for it exactly like
+//! `register_child` does, reports the server pid and then waits to be killed.
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use opencode_lib::watchdog::WatchdogState;
+
+const PARENT_ENV: &str = "OPENCODE_WATCHDOG_TEST_PARENT";
+
+fn is_alive(pid: u32) -> bool {
+    // A reaped process disappears from /proc; a zombie still shows up with state Z
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .map(|stat| {
+            stat.rsplit(')')
+                .next()
+                .and_then(|rest| rest.split_whitespace().next())
+                != Some("Z")
+        })
+        .unwrap_or(false)
+}
+
+#[test]
+#[ignore = "only runs as the fake desktop app spawned by sidecar_dies_with_sigkilled_app"]
+fn fake_desktop_app() {
+    if std::env::var_os(PARENT_ENV).is_none() {
+        return;
+    }
+
+    let mut server = Command::new("sleep")
+        .arg("600")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to spawn fake server");
+
+    let watchdogs = WatchdogState::new();
+    watchdogs.assign_pid(server.id());
+
+    println!("server-pid={}", server.id());
+
+    // Block until killed by the test
+    let _ = server.wait();
+}
+
+#[test]
+fn sidecar_dies_with_sigkilled_app() {
+    let mut app = Command::new(std::env::current_exe().unwrap())
+        .args(["fake_desktop_app", "--exact", "--ignored", "--nocapture"])
+        .env(PARENT_ENV, "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn fake desktop app");
+
+    let stdout = BufReader::new(app.stdout.take().unwrap());
+    let server_pid: u32 = stdout
+        .lines()
+        .map_while(Result::ok)
+        // libtest prints `test fake_desktop_app ... ` on the same line
+        .find_map(|line| {
+            line.split_once("server-pid=")
+                .map(|(_, pid)| pid.trim().parse().unwrap())
+        })
+        .expect("Fake desktop app did not report a server pid");
+
+    assert!(
+        is_alive(server_pid),
+        "Server should run while the app is alive"
+    );
+
+    // Child::kill sends SIGKILL, so no cleanup code in the app gets to run
+    app.kill().unwrap();
+    app.wait().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while is_alive(server_pid) {
+        assert!(
+            Instant::now() < deadline,
+            "Server {server_pid} outlived the SIGKILLed desktop app"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -13,7 +13,7 @@ import { AsyncStorage } from "@solid-primitives/storage"
 import { fetch as tauriFetch } from "@tauri-apps/plugin-http"
 import { Store } from "@tauri-apps/plugin-store"
 import { Logo } from "@opencode-ai/ui/logo"
-import { createSignal, Show, Accessor, JSX, createResource } from "solid-js"
+import { createSignal, Show, Accessor, JSX, createResource, onCleanup } from "solid-js"
 
 import { UPDATER_ENABLED } from "./updater"
 import { createMenu } from "./menu"
@@ -27,6 +27,7 @@ if (import.meta.env.DEV && !(root instanceof HTMLElement)) {
 }
 
 let update: Update | null = null
+let installed = false
 
 const createPlatform = (password: Accessor<string | null>): Platform => ({
   platform: "desktop",
@@ -216,12 +217,21 @@ const createPlatform = (password: Accessor<string | null>): Platform => ({
   update: async () => {
     if (!UPDATER_ENABLED || !update) return
     if (ostype() === "windows") await invoke("kill_sidecar").catch(() => undefined)
-    await update.install().catch(() => undefined)
+    installed = await update
+      .install()
+      .then(() => true)
+      .catch(() => false)
   },
 
   restart: async () => {
-    await invoke("kill_sidecar").catch(() => undefined)
-    await relaunch()
+    // A freshly installed update needs a new process; otherwise just respawn the server
+    if (installed) {
+      await invoke("kill_sidecar").catch(() => undefined)
+      await relaunch()
+      return
+    }
+    await invoke("restart_server").catch(() => undefined)
+    window.location.reload()
   },
 
   notify: async (title, description, href) => {
@@ -318,9 +328,37 @@ render(() => {
 
 type ServerReadyData = { url: string; password: string | null }
 
+type ServerPhase = "starting" | "ready" | "unhealthy" | "crashed" | "restarting" | "stopped"
+type ServerStatus = { phase: ServerPhase; url: string | null; message: string | null }
+
+const SERVER_PHASE_LABEL: Record<ServerPhase, string> = {
+  starting: "Initializing...",
+  ready: "Connecting...",
+  unhealthy: "Waiting for server...",
+  crashed: "Server crashed",
+  restarting: "Restarting server...",
+  stopped: "Server stopped",
+}
+
 // Gate component that waits for the server to be ready
 function ServerGate(props: { children: (data: Accessor<ServerReadyData>) => JSX.Element }) {
   const [serverData] = createResource<ServerReadyData>(() => invoke("ensure_server_ready"))
+  const [status, setStatus] = createSignal<ServerStatus | null>(null)
+
+  void invoke<ServerStatus>("get_server_status")
+    .then((next) => setStatus(next))
+    .catch(() => undefined)
+  // Server events are only sent to the windows that server backs
+  const win = getCurrentWindow()
+  const unlisten = Promise.all(
+    (Object.keys(SERVER_PHASE_LABEL) as ServerPhase[]).map((phase) =>
+      win.listen<ServerStatus>(`server://${phase}`, (event) => setStatus(event.payload)),
+    ),
+  )
+  // connect_to_server switched servers; reload so every client picks up the new one
+  const unlistenChanged = win.listen<ServerReadyData>("server://changed", () => window.location.reload())
+  onCleanup(() => void unlistenChanged.then((fn) => fn()))
+  onCleanup(() => void unlisten.then((fns) => fns.forEach((fn) => fn())))
 
   return (
     // Not using suspense as not all components are compatible with it (undefined refs)
@@ -329,7 +367,9 @@ function ServerGate(props: { children: (data: Accessor<ServerReadyData>) => JSX.
       fallback={
         <div class="h-screen w-screen flex flex-col items-center justify-center bg-background-base">
           <Logo class="w-xl opacity-12 animate-pulse" />
-          <div class="mt-8 text-14-regular text-text-weak">Initializing...</div>
+          <div class="mt-8 text-14-regular text-text-weak">
+            {SERVER_PHASE_LABEL[status()?.phase ?? "starting"]}
+          </div>
         </div>
       }
     >